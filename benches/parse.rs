@@ -0,0 +1,48 @@
+//! Parsing a sum of `terms` copies of one term, into an arena and into a tree of `f64`s.
+//!
+//! Measured when borrowed tokens were introduced, with `cargo bench --bench parse`:
+//!
+//! | terms | `to_nodes` before | `to_nodes` after | `arena` after |
+//! |------:|------------------:|-----------------:|--------------:|
+//! |    10 |           18.1 µs |          19.2 µs |        8.9 µs |
+//! |   100 |            157 µs |           193 µs |         73 µs |
+//! |  1000 |           1.81 ms |          2.04 ms |        714 µs |
+//!
+//! Building the tree dominates `to_nodes`, which did not get faster; parsing into an arena
+//! takes less than half the time.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn long_input(terms: usize) -> String {
+    let mut s = String::new();
+
+    for i in 0..terms {
+        if i != 0 {
+            s.push_str(" + ");
+        }
+
+        s.push_str("12345.678 * (2^3^0.5 - max(1, 2, π)) / 7e");
+    }
+
+    s
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for terms in [10, 100, 1000] {
+        let src = long_input(terms);
+
+        group.bench_with_input(BenchmarkId::new("arena", terms), &src, |b, src| {
+            b.iter(|| smolcalc::parse(src).unwrap().0.len())
+        });
+        group.bench_with_input(BenchmarkId::new("to_nodes", terms), &src, |b, src| {
+            b.iter(|| smolcalc::to_nodes::<f64>(src).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,20 +1,52 @@
 use smolcalc::*;
+use std::io::IsTerminal;
 
-fn evaluate<T: core::fmt::Display + traits::ComputableNumeral, F: Fn(T) -> String>(s: &str, f: F) {
-    match to_nodes::<T>(s) {
-        Ok(n) => match n.evaluate() {
-            Ok(v) => {
-                show_int(&n, s);
-                println!("= {}", f(v));
-            },
-            Err(e) => {
-                show_int(&n, s);
+fn evaluate<T: core::fmt::Display + traits::ComputableNumeral + traits::NumberEq + traits::NumberForm, F: Fn(T) -> String>(s: &str, f: F) {
+    let n = match Expr::parse(s) {
+        Ok(n) => n,
+        Err(e) => {
+            return match with_variables::<T>(s) {
+                Some(n) => simplify(&n, s),
+                None => report(s, e),
+            };
+        },
+    };
+
+    match n.evaluate::<T>() {
+        Ok(v) => {
+            show_int(&n.0, s);
+            println!("= {}", f(v));
+        },
+        Err(e) => match with_variables::<T>(s) {
+            Some(n) => simplify(&n, s),
+            None => {
+                show_int(&n.0, s);
                 report(s, e);
             },
         },
-        Err(e) => {
-            report(s, e);
-        }
+    }
+}
+
+/// The input parsed with variables if it has any, e.g. `x` in `2x + 3x`. Longer names are more
+/// likely misspelled constants or functions, so every variable has to be a single letter.
+fn with_variables<T: Clone + traits::Numeral>(s: &str) -> Option<Node<T>> {
+    let n = to_nodes_with_variables::<T>(s).ok()?;
+    let v = n.variables();
+
+    (!v.is_empty() && v.iter().all(|v| v.chars().count() == 1)).then_some(n)
+}
+
+fn simplify<T: core::fmt::Display + traits::ComputableNumeral + traits::NumberEq + traits::NumberForm>(n: &Node<T>, s: &str) {
+    show_int(n, s);
+
+    match n.simplify() {
+        Ok(v) => {
+            println!("= {}", v.node);
+            for c in v.conditions {
+                println!("  for {c} ≠ 0");
+            }
+        },
+        Err(e) => report(s, e),
     }
 }
 
@@ -41,13 +73,13 @@ fn main() {
     let mode = args.next();
     let expr = args.collect::<Vec<String>>().join(" ");
 
-    match mode.as_deref() {
-        Some("f32") => evaluate::<f32, _>(&expr, |a| trunc(&format!("{a:.5}")).to_string()),
-        Some("f64") => evaluate::<f64, _>(&expr, |a| trunc(&format!("{a:.13}")).to_string()),
-        Some("rat") => evaluate::<rational::Rational<num_bigint::BigInt>, _>(&expr, |a| format!("{a:#}")),
-        Some("c32") => evaluate::<num_complex::Complex<f32>, _>(&expr, |a| pretty_cmplx(a, |a| trunc(&format!("{a:.5}")).to_string())),
-        Some("c64") => evaluate::<num_complex::Complex<f64>, _>(&expr, |a| pretty_cmplx(a, |a| trunc(&format!("{a:.13}")).to_string())),
-        Some("crat") => evaluate::<rational::complex::ComplexRational<num_bigint::BigInt>, _>(&expr, |a| format!("{a:#}")),
+    let eval: fn(&str) = match mode.as_deref() {
+        Some("f32") => |s| evaluate::<f32, _>(s, |a| trunc(&format!("{a:.5}")).to_string()),
+        Some("f64") => |s| evaluate::<f64, _>(s, |a| trunc(&format!("{a:.13}")).to_string()),
+        Some("rat") => |s| evaluate::<rational::Rational<num_bigint::BigInt>, _>(s, |a| format!("{a:#}")),
+        Some("c32") => |s| evaluate::<num_complex::Complex<f32>, _>(s, |a| pretty_cmplx(a, |a| trunc(&format!("{a:.5}")).to_string())),
+        Some("c64") => |s| evaluate::<num_complex::Complex<f64>, _>(s, |a| pretty_cmplx(a, |a| trunc(&format!("{a:.13}")).to_string())),
+        Some("crat") => |s| evaluate::<rational::complex::ComplexRational<num_bigint::BigInt>, _>(s, |a| format!("{a:#}")),
         Some(m) => {
             println!("\x1b[1;31mError:\x1b[0m mode `{m}` not supported!");
             std::process::exit(1);
@@ -57,6 +89,8 @@ fn main() {
             std::process::exit(1);
         },
     };
+
+    eval(&expr);
 }
 
 fn trunc(s: &str) -> &str {
@@ -67,11 +101,16 @@ fn trunc(s: &str) -> &str {
     }
 }
 
-fn show_int<T: core::fmt::Display>(i: &Node<T>, src: &str) {
-    println!("\x1b[1mInput interpretation:\x1b[0m ${}$", latex::LatexDisplay {
-        node: i,
-        src,
-    });
+fn show_int<T: std::fmt::Display + traits::NumberForm>(i: &Node<T>, src: &str) {
+    if std::io::stdout().is_terminal() {
+        let pretty = pretty::PrettyDisplay { node: i, src }.to_string();
+        println!("\x1b[1mInput interpretation:\x1b[0m\n  {}", pretty.replace('\n', "\n  "));
+    } else {
+        println!("\x1b[1mInput interpretation:\x1b[0m ${}$", latex::LatexDisplay {
+            node: i,
+            src,
+        });
+    }
 }
 
 fn pretty_cmplx<T: num_traits::Float + num_traits::Signed, F: Fn(T) -> String>(
@@ -0,0 +1,17 @@
+use std::process::Command;
+
+fn calculate(mode: &str, s: &str) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_calculate")).args([mode, s]).output().unwrap();
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn variables() {
+    assert!(calculate("f64", "2x + 3x").ends_with("= 5x\n"));
+    assert!(calculate("rat", "x/x").ends_with("= 1\n  for x ≠ 0\n"));
+
+    for s in ["ln(-1)", "foo(2)", "abc", "1 + i"] {
+        let out = calculate("f64", s);
+        assert!(out.contains("Error:") && !out.contains("= "), "{s}: {out}");
+    }
+}
@@ -39,7 +39,6 @@ document.addEventListener("DOMContentLoaded", async (_) => {
 
         await import("https://cdn.jsdelivr.net/npm/katex@0.16.10/dist/katex.min.mjs").then((resp) => {
             katex = resp.default;
-            KATEX_P.style.display = "initial";
         });
     }
 
@@ -55,16 +54,21 @@ document.addEventListener("DOMContentLoaded", async (_) => {
         let e = evaluate(INPUT.value);
         OUTPUT.innerHTML = e.output;
 
-        if (katex !== undefined) {
-            if (e.latex != "") {
-                KATEX_P.style.display = "initial";
+        if (e.latex != "") {
+            KATEX_P.style.display = "initial";
+            document.getElementById("ii").setAttribute("aria-label", e.speech);
+
+            if (katex !== undefined) {
                 katex.render(e.latex, document.getElementById("ii"), {
                     throwOnError: false,
                     output: "html",
                 });
             } else {
-                KATEX_P.style.display = "none";
+                // the markup is escaped in rust side
+                document.getElementById("ii").innerHTML = e.mathml;
             }
+        } else {
+            KATEX_P.style.display = "none";
         }
 
         let typ = window.location.hash.slice(1).split("-", 1)[0];
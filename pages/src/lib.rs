@@ -26,28 +26,66 @@ fn report(src: &str, err: Error) -> String {
     )
 }
 
-fn evaluate<T: ComputableNumeral, F: Fn(T) -> String>(s: &str, f: F) -> Eval {
+fn evaluate<T: ComputableNumeral + NumberEq + NumberForm + std::fmt::Display, F: Fn(T) -> String>(s: &str, f: F) -> Eval {
     match to_nodes::<T>(s) {
         Ok(n) => match n.evaluate() {
-            Ok(v) => Eval {
-                output: format!("= {}", f(v)),
-                latex: latex::LatexDisplay {
-                    node: &n,
-                    src: s,
-                }.to_string(),
+            Ok(v) => interpreted(&n, s, format!("= {}", f(v))),
+            Err(e) => match with_variables::<T>(s) {
+                Some(n) => simplify(&n, s),
+                None => interpreted(&n, s, report(s, e)),
             },
-            Err(e) => Eval {
+        },
+        Err(e) => match with_variables::<T>(s) {
+            Some(n) => simplify(&n, s),
+            None => Eval {
                 output: report(s, e),
-                latex: latex::LatexDisplay {
-                    node: &n,
-                    src: s,
-                }.to_string(),
+                latex: String::new(),
+                mathml: String::new(),
+                speech: String::new(),
             },
         },
-        Err(e) => Eval {
-            output: report(s, e),
-            latex: String::new(),
-        }
+    }
+}
+
+/// The input parsed with variables if it has any, e.g. `x` in `2x + 3x`. Longer names are more
+/// likely misspelled constants or functions, so every variable has to be a single letter.
+fn with_variables<T: Clone + Numeral>(s: &str) -> Option<Node<T>> {
+    let n = to_nodes_with_variables::<T>(s).ok()?;
+    let v = n.variables();
+
+    (!v.is_empty() && v.iter().all(|v| v.chars().count() == 1)).then_some(n)
+}
+
+fn simplify<T: ComputableNumeral + NumberEq + NumberForm + std::fmt::Display>(n: &Node<T>, s: &str) -> Eval {
+    let output = match n.simplify() {
+        Ok(v) => {
+            let mut output = sanitize(&format!("= {}", v.node));
+            for c in v.conditions {
+                output += &sanitize(&format!("\n  for {c} ≠ 0"));
+            }
+            output
+        },
+        Err(e) => report(s, e),
+    };
+
+    interpreted(n, s, output)
+}
+
+fn interpreted<T: std::fmt::Display + NumberForm>(n: &Node<T>, s: &str, output: String) -> Eval {
+    Eval {
+        output,
+        latex: latex::LatexDisplay {
+            node: n,
+            src: s,
+        }.to_string(),
+        mathml: mathml::MathMlDisplay {
+            node: n,
+            src: s,
+        }.to_string(),
+        speech: speech::SpokenDisplay {
+            node: n,
+            src: s,
+        }.to_string(),
     }
 }
 
@@ -58,6 +96,8 @@ fn evaluate<T: ComputableNumeral, F: Fn(T) -> String>(s: &str, f: F) -> Eval {
 pub struct Eval {
     pub output: String,
     pub latex: String,
+    pub mathml: String,
+    pub speech: String,
 }
 
 #[wasm_bindgen]
@@ -112,3 +152,22 @@ fn trunc(s: &str) -> &str {
         _ => s,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables() {
+        for (s, expected) in [("2x + 3x", "= 5x"), ("x/x", "= 1\n  for x ≠ 0")] {
+            assert_eq!(evaluate_f64(s).output, expected);
+        }
+
+        for s in ["ln(-1)", "foo(2)", "abc", "1 + i"] {
+            let e = evaluate_f64(s);
+            assert!(e.output.contains("Error:"), "{s}: {}", e.output);
+        }
+
+        assert!(evaluate_rational("2 + 3").output.starts_with("= 5"));
+    }
+}
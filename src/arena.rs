@@ -0,0 +1,509 @@
+//! Index-based expression trees. An [`Arena`] either grows on the heap or lives in a buffer supplied
+//! by the caller, in which case parsing and evaluation do not allocate.
+//!
+//! Nodes are only ever appended, so a [`NodeId`] stays valid until the arena is cleared. Children
+//! are always added before their parents. Additional data can be attached to nodes with a
+//! [`SideTable`].
+
+use crate::*;
+use core::ops::Index;
+
+/// The most arguments a function can be called with in [`Arena::evaluate`]
+pub const MAX_ARGS: usize = 16;
+
+/// The deepest a tree can be nested. Deeper input is rejected with [`ErrorKind::TooDeep`], so that
+/// parsing, evaluating and rendering it can not overflow the stack. A chain of left-associative
+/// operations of the same precedence such as `1 + 2 - 3` counts as a single level, so that long
+/// sums and products are accepted. Evaluating and rendering them still recurses once per operand.
+pub const MAX_DEPTH: usize = 256;
+
+/// An unoccupied slot, for initializing the buffer of an [`Arena`], e.g. `[arena::EMPTY; 64]`
+pub const EMPTY: Slot<'static> = None;
+
+pub type Slot<'src> = Option<ArenaNode<'src>>;
+
+/// Index of a node in an [`Arena`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize { self.0 }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArenaNode<'src> {
+    pub kind: ArenaKind<'src>,
+    pub span: Span,
+    /// The next argument, if this node is an argument of a function
+    pub next: Option<NodeId>,
+    /// How many levels deep the tree at this node is
+    depth: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArenaKind<'src> {
+    /// A number as written in the source, converted only when evaluating
+    Number(&'src str),
+    Constant(&'src str),
+    BiOp(NodeId, BiOpr, NodeId),
+    UnOp(UnOpr, NodeId),
+    /// A function, its bracket, where its name ends and its first argument. The rest of the
+    /// arguments are chained by [`ArenaNode::next`].
+    Function(&'src str, BKind, usize, Option<NodeId>),
+    /// An expression in brackets
+    Group(BKind, NodeId),
+}
+
+enum Slots<'buf, 'src> {
+    Fixed(&'buf mut [Slot<'src>]),
+    Growable(Vec<Slot<'src>>),
+}
+
+/// A store of nodes
+pub struct Arena<'buf, 'src> {
+    slots: Slots<'buf, 'src>,
+    len: usize,
+}
+
+impl<'buf, 'src> Arena<'buf, 'src> {
+    /// Creates a fixed-capacity arena that keeps its nodes in `slots`
+    pub fn new(slots: &'buf mut [Slot<'src>]) -> Self { Self { slots: Slots::Fixed(slots), len: 0 } }
+
+    /// Creates an arena that allocates its nodes on the heap
+    pub fn growable() -> Self { Self { slots: Slots::Growable(Vec::new()), len: 0 } }
+
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// How many nodes fit in the arena without growing it
+    pub fn capacity(&self) -> usize {
+        match &self.slots {
+            Slots::Fixed(s) => s.len(),
+            Slots::Growable(s) => s.capacity(),
+        }
+    }
+
+    /// Removes every node. Previously handed out [`NodeId`]s are invalidated.
+    pub fn clear(&mut self) { self.len = 0; }
+
+    /// Parses `src` into the arena and returns the root node
+    pub fn parse(&mut self, src: &'src str) -> Result<NodeId, Error> { self.parse_with(src, Dialect::Plain) }
+
+    pub fn parse_with(&mut self, src: &'src str, dialect: Dialect) -> Result<NodeId, Error> {
+        let mut lex = Tokens::new(src, dialect);
+        parse_expr(&mut lex, self)
+    }
+
+    /// Parses `src` with unknown names as [`ArenaKind::Constant`]s, a name is a run of letters
+    pub fn parse_with_variables(&mut self, src: &'src str) -> Result<NodeId, Error> {
+        self.parse_with_variables_in(src, Dialect::Plain)
+    }
+
+    /// Like [`Arena::parse_with_variables`] in `dialect`, where a name is a single letter
+    pub fn parse_with_variables_in(&mut self, src: &'src str, dialect: Dialect) -> Result<NodeId, Error> {
+        let mut lex = Tokens::new(src, dialect);
+        lex.lexer.variables = true;
+        parse_expr(&mut lex, self)
+    }
+
+    /// Iterates over the arguments of a function, starting from its first argument
+    pub fn args(&self, first: Option<NodeId>) -> impl Iterator<Item = NodeId> + use<'_, 'buf, 'src> {
+        core::iter::successors(first, |&a| self[a].next)
+    }
+
+    /// Iterates over every node in the order they were added, so children come before their
+    /// parents
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> + use<> { (0..self.len).map(NodeId) }
+
+    /// Iterates over the direct children of a node from left to right
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + use<'_, 'buf, 'src> {
+        let (a, b, args) = match self[id].kind {
+            ArenaKind::Number(_) | ArenaKind::Constant(_) => (None, None, None),
+            ArenaKind::BiOp(l, _, r) => (Some(l), Some(r), None),
+            ArenaKind::UnOp(_, v) | ArenaKind::Group(_, v) => (Some(v), None, None),
+            ArenaKind::Function(.., first) => (None, None, first),
+        };
+
+        a.into_iter().chain(b).chain(self.args(args))
+    }
+
+    /// Iterates over the tree at `root`, visiting parents before their children
+    pub fn preorder(&self, root: NodeId) -> impl Iterator<Item = NodeId> + use<'_, 'buf, 'src> {
+        let mut stack = alloc::vec![root];
+
+        core::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let len = stack.len();
+            stack.extend(self.children(id));
+            stack[len..].reverse();
+
+            Some(id)
+        })
+    }
+
+    /// Iterates over the tree at `root`, visiting children before their parents
+    pub fn postorder(&self, root: NodeId) -> impl Iterator<Item = NodeId> + use<'_, 'buf, 'src> {
+        let mut stack = alloc::vec![(root, false)];
+
+        core::iter::from_fn(move || loop {
+            let (id, expanded) = stack.pop()?;
+
+            if expanded {
+                return Some(id);
+            }
+
+            stack.push((id, true));
+            let len = stack.len();
+            stack.extend(self.children(id).map(|c| (c, false)));
+            stack[len..].reverse();
+        })
+    }
+
+    /// Finds the parent of every node in the tree at `root`
+    pub fn parents(&self, root: NodeId) -> SideTable<NodeId> {
+        let mut parents = SideTable::new();
+
+        for id in self.preorder(root) {
+            for c in self.children(id) {
+                parents.insert(c, id);
+            }
+        }
+
+        parents
+    }
+
+    pub(crate) fn push(&mut self, kind: ArenaKind<'src>, span: Span) -> Result<NodeId, Error> {
+        let depth = 1 + match kind {
+            ArenaKind::Number(_) | ArenaKind::Constant(_) => 0,
+            // the left operand continues a chain such as `1 + 2 - 3`
+            ArenaKind::BiOp(l, op, r) => match self[l].kind {
+                ArenaKind::BiOp(_, lop, _) if op.is_left_associative() && lop.percedence() == op.percedence() => {
+                    (self[l].depth - 1).max(self[r].depth)
+                },
+                _ => self[l].depth.max(self[r].depth),
+            },
+            ArenaKind::UnOp(_, v) | ArenaKind::Group(_, v) => self[v].depth,
+            ArenaKind::Function(.., first) => self.args(first).map(|a| self[a].depth).max().unwrap_or(0),
+        };
+
+        if depth > MAX_DEPTH {
+            return Err(Error::too_deep(span));
+        }
+
+        let node = ArenaNode {
+            kind,
+            span,
+            next: None,
+            depth,
+        };
+
+        match &mut self.slots {
+            Slots::Fixed(s) => match s.get_mut(self.len) {
+                Some(slot) => *slot = Some(node),
+                None => return Err(Error::capacity_exceeded(node.span)),
+            },
+            Slots::Growable(s) => {
+                s.truncate(self.len);
+                s.push(Some(node));
+            },
+        }
+
+        self.len += 1;
+
+        Ok(NodeId(self.len - 1))
+    }
+
+    pub(crate) fn set_next(&mut self, id: NodeId, next: NodeId) {
+        let slot = match &mut self.slots {
+            Slots::Fixed(s) => &mut s[id.0],
+            Slots::Growable(s) => &mut s[id.0],
+        };
+
+        if let Some(node) = slot {
+            node.next = Some(next);
+        }
+    }
+
+    /// Converts the tree at `id` into a heap-allocated [`Node`]
+    pub fn to_node<T: Numeral>(&self, id: NodeId) -> Result<Node<T>, Error> {
+        self.build(id, &|n, span| parse_number(n, span))
+    }
+
+    /// Builds an [`Expr`] that keeps the literals as they were written
+    pub fn to_expr(&self, id: NodeId) -> Expr {
+        let expr = self.build(id, &|n, _| Ok::<_, core::convert::Infallible>(Literal(n.into())));
+
+        Expr(expr.unwrap_or_else(|e| match e {}))
+    }
+
+    fn build<N, E>(
+        &self,
+        id: NodeId,
+        number: &impl Fn(&'src str, &Span) -> Result<N, E>,
+    ) -> Result<Node<N>, E> {
+        // the left operands of a chain such as `1 + 2 + 3` are built in a loop, so that long chains
+        // do not recurse
+        let mut chain = Vec::new();
+        let mut id = id;
+        while let ArenaKind::BiOp(l, op, r) = self[id].kind {
+            chain.push((id, op, r));
+            id = l;
+        }
+
+        let mut built = self.build_operand(id, number)?;
+        for (id, op, r) in chain.into_iter().rev() {
+            built = Node {
+                kind: NodeKind::BiOp(Box::new(built), op, Box::new(self.build(r, number)?)),
+                span: self[id].span.clone(),
+            };
+        }
+
+        Ok(built)
+    }
+
+    fn build_operand<N, E>(
+        &self,
+        id: NodeId,
+        number: &impl Fn(&'src str, &Span) -> Result<N, E>,
+    ) -> Result<Node<N>, E> {
+        let node = &self[id];
+        let kind = match node.kind {
+            ArenaKind::Number(n) => NodeKind::Number(number(n, &node.span)?),
+            ArenaKind::Constant(c) => NodeKind::Constant(c.into()),
+            ArenaKind::BiOp(..) => return self.build(id, number),
+            ArenaKind::UnOp(op, v) => NodeKind::UnOp(op, Box::new(self.build(v, number)?)),
+            ArenaKind::Function(f, bracket, name_end, first) => NodeKind::Function(Call {
+                name: f.into(),
+                name_span: node.span.start..name_end,
+                bracket,
+                args_span: name_end..node.span.end,
+                args: self.args(first).map(|a| self.build(a, number)).collect::<Result<_, _>>()?,
+            }),
+            ArenaKind::Group(k, v) => NodeKind::Group(k, Box::new(self.build(v, number)?)),
+        };
+
+        Ok(Node {
+            kind,
+            span: node.span.clone(),
+        })
+    }
+
+    #[cfg(not(feature = "any_num"))]
+    pub fn evaluate(&self, id: NodeId) -> Result<f32, Error> {
+        let node = &self[id];
+
+        match node.kind {
+            ArenaKind::Number(n) => parse_number(n, &node.span),
+            ArenaKind::Constant(c) => constant(c, &node.span),
+            ArenaKind::BiOp(l, op, r) => Ok(op.operate(self.evaluate(l)?, self.evaluate(r)?)),
+            ArenaKind::UnOp(op, v) => Ok(op.operate(self.evaluate(v)?)),
+            ArenaKind::Group(_, v) => self.evaluate(v),
+            ArenaKind::Function(f, _, _, first) => {
+                let mut av = [0.0; MAX_ARGS];
+                let len = self.evaluate_args(first, &mut av, &node.span)?;
+
+                f32::execute(f, &av[..len]).map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
+                    location: node.span.clone(),
+                })
+            },
+        }
+    }
+
+    #[cfg(feature = "any_num")]
+    pub fn evaluate<F: ComputableNumeral>(&self, id: NodeId) -> Result<F, Error> {
+        let node = &self[id];
+
+        match node.kind {
+            ArenaKind::Number(n) => parse_number(n, &node.span),
+            ArenaKind::Constant(c) => constant(c, &node.span),
+            ArenaKind::BiOp(l, op, r) => op
+                .operate(self.evaluate(l)?, self.evaluate(r)?)
+                .map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
+                    location: node.span.clone(),
+                }),
+            ArenaKind::UnOp(op, v) => op.operate(self.evaluate(v)?).map_err(|message| Error {
+                kind: ErrorKind::Evaluation,
+                message: message.into(),
+                location: node.span.clone(),
+            }),
+            ArenaKind::Group(_, v) => self.evaluate(v),
+            ArenaKind::Function(f, _, _, first) => {
+                let mut av: [F; MAX_ARGS] = core::array::from_fn(|_| F::zero());
+                let len = self.evaluate_args(first, &mut av, &node.span)?;
+
+                F::execute(f, &av[..len]).map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
+                    location: node.span.clone(),
+                })
+            },
+        }
+    }
+
+    #[cfg(not(feature = "any_num"))]
+    fn evaluate_args(&self, first: Option<NodeId>, av: &mut [f32], span: &Span) -> Result<usize, Error> {
+        let mut len = 0;
+
+        for a in self.args(first) {
+            *av.get_mut(len).ok_or_else(|| Error::capacity_exceeded(span.clone()))? = self.evaluate(a)?;
+            len += 1;
+        }
+
+        Ok(len)
+    }
+
+    #[cfg(feature = "any_num")]
+    fn evaluate_args<F: ComputableNumeral>(
+        &self,
+        first: Option<NodeId>,
+        av: &mut [F],
+        span: &Span,
+    ) -> Result<usize, Error> {
+        let mut len = 0;
+
+        for a in self.args(first) {
+            *av.get_mut(len).ok_or_else(|| Error::capacity_exceeded(span.clone()))? = self.evaluate(a)?;
+            len += 1;
+        }
+
+        Ok(len)
+    }
+}
+
+impl<'src> Index<NodeId> for Arena<'_, 'src> {
+    type Output = ArenaNode<'src>;
+
+    fn index(&self, id: NodeId) -> &ArenaNode<'src> {
+        let slots = match &self.slots {
+            Slots::Fixed(s) => &s[..self.len],
+            Slots::Growable(s) => &s[..self.len],
+        };
+
+        slots[id.0].as_ref().expect("occupied slot")
+    }
+}
+
+/// Values attached to the nodes of an [`Arena`]
+#[derive(Debug, Clone)]
+pub struct SideTable<V> {
+    values: Vec<Option<V>>,
+}
+
+impl<V> SideTable<V> {
+    pub fn new() -> Self { Self { values: Vec::new() } }
+
+    /// Attaches `value` to the node `id`, returning the value previously attached to it
+    pub fn insert(&mut self, id: NodeId, value: V) -> Option<V> {
+        if self.values.len() <= id.0 {
+            self.values.resize_with(id.0 + 1, || None);
+        }
+
+        self.values[id.0].replace(value)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&V> { self.values.get(id.0)?.as_ref() }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut V> { self.values.get_mut(id.0)?.as_mut() }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<V> { self.values.get_mut(id.0)?.take() }
+
+    pub fn contains(&self, id: NodeId) -> bool { self.get(id).is_some() }
+
+    /// Iterates over every node that has a value attached
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &V)> {
+        self.values.iter().enumerate().filter_map(|(i, v)| Some((NodeId(i), v.as_ref()?)))
+    }
+}
+
+impl<V> Default for SideTable<V> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<V> Index<NodeId> for SideTable<V> {
+    type Output = V;
+
+    fn index(&self, id: NodeId) -> &V { self.get(id).expect("node has a value attached") }
+}
+
+pub(crate) fn parse_number<T: Numeral>(n: &str, span: &Span) -> Result<T, Error> {
+    n.parse().map_err(|_| Error {
+        kind: ErrorKind::Syntax,
+        message: "number format is incorrect".into(),
+        location: span.clone(),
+    })
+}
+
+pub(crate) fn constant<T: Numeral>(c: &str, span: &Span) -> Result<T, Error> {
+    T::from_constant(c).ok_or_else(|| Error {
+        kind: ErrorKind::Evaluation,
+        message: "this constant is not supported".into(),
+        location: span.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_capacity() {
+        let mut slots = [EMPTY; 16];
+        let mut arena = Arena::new(&mut slots);
+        let root = arena.parse("2^3^2 + max(1, 2, 3)π/π").unwrap();
+        let v: f32 = arena.evaluate(root).unwrap();
+        assert_eq!(v, 515.0);
+
+        let mut slots = [EMPTY; 4];
+        let mut arena = Arena::new(&mut slots);
+        assert!(arena.parse("1 + 2 + 3").unwrap_err().is_capacity_exceeded());
+
+        let mut slots = [EMPTY; 32];
+        let mut arena = Arena::new(&mut slots);
+        let root = arena.parse("min(1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17)").unwrap();
+        let v: Result<f32, _> = arena.evaluate(root);
+        assert!(v.unwrap_err().is_capacity_exceeded());
+
+        let mut slots = [EMPTY; 16];
+        let mut arena = Arena::new(&mut slots);
+        assert_eq!(arena.parse("1 + )").unwrap_err().kind, ErrorKind::Syntax);
+        let root = arena.parse("min()").unwrap();
+        let v: Result<f32, _> = arena.evaluate(root);
+        assert_eq!(v.unwrap_err().kind, ErrorKind::Evaluation);
+    }
+
+    #[test]
+    fn traversal() {
+        let (arena, root) = crate::parse("-2^3 + f(1, 2)").unwrap();
+        let kind = |id: NodeId| match arena[id].kind {
+            ArenaKind::Number(n) => n,
+            ArenaKind::Constant(c) | ArenaKind::Function(c, ..) => c,
+            ArenaKind::BiOp(..) => "bi",
+            ArenaKind::UnOp(..) => "un",
+            ArenaKind::Group(..) => "group",
+        };
+
+        let pre: Vec<_> = arena.preorder(root).map(kind).collect();
+        assert_eq!(pre, ["bi", "bi", "un", "2", "3", "f", "1", "2"]);
+        let post: Vec<_> = arena.postorder(root).map(kind).collect();
+        assert_eq!(post, ["2", "un", "3", "bi", "1", "2", "f", "bi"]);
+
+        let mut depth = SideTable::new();
+        let parents = arena.parents(root);
+        for id in arena.preorder(root) {
+            depth.insert(id, parents.get(id).map_or(0, |p| depth[*p] + 1));
+        }
+        assert_eq!(depth.iter().map(|(_, d)| *d).max(), Some(3));
+        assert!(!parents.contains(root));
+
+        // a long right-associative chain is built with one node per operand and operator
+        let chain = "2^".repeat(MAX_DEPTH - 1) + "2";
+        let (arena, root) = crate::parse(&chain).unwrap();
+        assert_eq!(arena.len(), 2 * MAX_DEPTH - 1);
+        assert_eq!(arena.postorder(root).count(), 2 * MAX_DEPTH - 1);
+    }
+}
@@ -0,0 +1,112 @@
+//! Words of the ASCIIMath input dialect
+
+/// Words that are operators
+const OPERATORS: &[&str] = &["xx", "mod", "frac", "root"];
+
+/// Functions, their arguments may or may not be in brackets, e.g. `sqrt x`
+pub(crate) const FUNCTIONS: &[&str] = &[
+    "sqrt", "abs", "floor", "ceil", "norm", "sin", "cos", "tan", "sinh", "cosh", "tanh", "arcsin", "arccos",
+    "arctan", "ln", "log", "exp", "max", "min",
+];
+
+/// Constants and what the parser calls them
+const CONSTANTS: &[(&str, &str)] = &[
+    ("pi", "π"),
+    ("tau", "τ"),
+    ("phi", "φ"),
+    ("varphi", "ϕ"),
+    ("gamma", "γ"),
+    ("e", "e"),
+    ("i", "i"),
+    ("oo", "∞"),
+];
+
+/// Words that have no meaning as an expression, e.g. `sum_(i=1)^n`
+const UNSUPPORTED: &[&str] = &["sum", "prod", "int", "oint", "lim", "del", "grad"];
+
+/// The longest word that `s` starts with
+pub(crate) fn word(s: &str) -> Option<&'static str> {
+    OPERATORS
+        .iter()
+        .chain(FUNCTIONS)
+        .chain(CONSTANTS.iter().map(|(w, _)| w))
+        .chain(UNSUPPORTED)
+        .filter(|w| s.starts_with(**w))
+        .max_by_key(|w| w.len())
+        .copied()
+}
+
+pub(crate) fn constant(w: &str) -> Option<&'static str> {
+    CONSTANTS.iter().find(|(c, _)| *c == w).map(|(_, c)| *c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn same_as_native() {
+        for (s, native) in [
+            ("sqrt 2", "sqrt(2)"),
+            ("sqrt(2) + 1", "sqrt(2)+1"),
+            ("sqrt 2pi", "sqrt(2)π"),
+            ("2^(3+1)", "2^(3+1)"),
+            ("root(3)(8)", "cbrt(8)"),
+            ("root(2)(8)", "sqrt(8)"),
+            ("root(4)(16)", "16^(1/4)"),
+            ("root 5 2", "2^(1/5)"),
+            ("pi xx 2 ** 3 * tau", "π*2*3*τ"),
+            ("6 -: 3 // 2", "6/3/2"),
+            ("7 mod 4", "7%4"),
+            ("frac{1}{2} + frac 3 4", "1/2+3/4"),
+            ("abs(-1) + oo", "abs(-1)+∞"),
+            ("sin pi/2", "sin(π)/2"),
+            ("sin 2^2", "sin(2^2)"),
+            ("-2^2", "-(2^2)"),
+            ("max(1, 2e)", "max(1, 2e)"),
+            ("ln 2", "ln(2)"),
+        ] {
+            let a = to_nodes_with::<f64>(s, Dialect::AsciiMath).unwrap();
+            let b = to_nodes::<f64>(native).unwrap();
+            assert_eq!(a.to_string(), b.to_string(), "{s}");
+        }
+
+        let v = to_nodes_with::<f32>("1 / oo", Dialect::AsciiMath).unwrap().evaluate().unwrap();
+        assert_eq!(v, 0.0);
+    }
+
+    #[test]
+    fn variables() {
+        for (s, native) in [
+            ("sqrt x", "sqrt(x)"),
+            ("x^(n+1)", "x^(n+1)"),
+            ("root(3)(x)", "cbrt(x)"),
+            ("abs(x)", "abs(x)"),
+            ("2xy + sin x", "2x y+sin(x)"),
+            ("pix", "π x"),
+        ] {
+            let a = to_nodes_with_variables_in::<f64>(s, Dialect::AsciiMath).unwrap();
+            let b = to_nodes_with_variables::<f64>(native).unwrap();
+            assert_eq!(a, b, "{s}");
+        }
+
+        let e = to_nodes_with_variables_in::<f64>("sum_(i=1)^n i", Dialect::AsciiMath).unwrap_err();
+        assert_eq!(e.location, 0..3);
+    }
+
+    #[test]
+    fn unsupported() {
+        for (s, location) in [
+            ("sum_(i=1)^n i", 0..3),
+            ("2 + x", 4..5),
+            ("pix", 2..3),
+            ("int 2 dx", 0..3),
+            ("|1|", 0..3),
+            ("frac 1", 6..7),
+        ] {
+            let e = to_nodes_with::<f64>(s, Dialect::AsciiMath).unwrap_err();
+            assert_eq!(e.location, location, "{s}");
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Building trees in Rust, e.g. `num(2.0) * var("x") + func("sin", [var("y")])`.
+//!
+//! Built nodes have empty spans at the start of the source, so the renderers display their numbers
+//! instead of taking them from a source.
+
+use core::ops;
+use alloc::{boxed::Box, string::String};
+use crate::*;
+
+const SPAN: Span = 0..0;
+
+fn node<N>(kind: NodeKind<N>) -> Node<N> { Node { kind, span: SPAN } }
+
+pub fn num<N>(n: impl Into<N>) -> Node<N> { node(NodeKind::Number(n.into())) }
+
+/// A named value, either a constant such as `π` or a variable
+pub fn var<N>(name: &str) -> Node<N> { node(NodeKind::Constant(name.into())) }
+
+pub fn func<N>(name: &str, args: impl IntoIterator<Item = Node<N>>) -> Node<N> {
+    node(NodeKind::Function(Call {
+        name: String::from(name),
+        name_span: SPAN,
+        bracket: BKind::Round,
+        args_span: SPAN,
+        args: args.into_iter().collect(),
+    }))
+}
+
+impl<N> Node<N> {
+    pub fn pow(self, exp: Node<N>) -> Node<N> { node(NodeKind::BiOp(Box::new(self), BiOpr::Power, Box::new(exp))) }
+}
+
+macro_rules! bi_op {
+    ($($t: ident $f: ident $op: ident),*) => {$(
+        impl<N> ops::$t for Node<N> {
+            type Output = Node<N>;
+
+            fn $f(self, rhs: Node<N>) -> Node<N> { node(NodeKind::BiOp(Box::new(self), BiOpr::$op, Box::new(rhs))) }
+        }
+    )*};
+}
+
+bi_op!(Add add Add, Sub sub Subtract, Mul mul Multiply, Div div Divide, Rem rem Modulo);
+
+impl<N> ops::Neg for Node<N> {
+    type Output = Node<N>;
+
+    fn neg(self) -> Node<N> { node(NodeKind::UnOp(UnOpr::Minus, Box::new(self))) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn built() {
+        let n: Node<f64> = num(2) * var("π") + func("sqrt", [num(4)]) - (-num(1.5)).pow(num(2));
+        assert_eq!(n, to_nodes::<f64>("2*π + sqrt(4) - -1.5^2").unwrap());
+        assert_eq!(n.to_string(), "2 * π + sqrt(4) - -1.5^2");
+
+        let n: Node<f64> = (num(1) + num(2)) * num(3) % num(4) / var("x");
+        assert_eq!(n.to_string(), "(1 + 2) * 3 % 4 / x");
+        assert_eq!(latex::LatexDisplay { node: &n, src: "" }.to_string(), r"\frac{\left(1 + 2\right) \cdot 3 \bmod 4}{x}");
+        assert_eq!(pretty::PrettyDisplay { node: &n, src: "" }.to_string(), "(1 + 2) ⋅ 3 mod 4\n─────────────────\n        x");
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn evaluate() {
+        let n: Node<f64> = num(2) * var("π") + func("max", [num(1), num(4)]).pow(num(0.5));
+        assert_eq!(n.evaluate().unwrap(), 2.0 * core::f64::consts::PI + 2.0);
+    }
+}
@@ -1,7 +1,49 @@
+use alloc::borrow::Cow;
 use crate::Span;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Error {
-    pub message: &'static str,
+    pub kind: ErrorKind,
+    /// Errors made by this crate borrow their message, deserialized ones own it
+    pub message: Cow<'static, str>,
     pub location: Span,
 }
+
+/// What went wrong, so that callers can react to an error without comparing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    /// The input is not a valid expression
+    Syntax,
+    /// The expression is valid but has no value, e.g. because it divides by zero
+    Evaluation,
+    /// A fixed-capacity buffer is too small
+    CapacityExceeded,
+    /// The expression is nested deeper than [`crate::arena::MAX_DEPTH`]
+    TooDeep,
+}
+
+impl Error {
+    pub const CAPACITY_EXCEEDED: &'static str = "capacity exceeded";
+
+    /// An error caused by a fixed-capacity buffer being too small
+    pub fn capacity_exceeded(location: Span) -> Self {
+        Self {
+            kind: ErrorKind::CapacityExceeded,
+            message: Cow::Borrowed(Self::CAPACITY_EXCEEDED),
+            location,
+        }
+    }
+
+    /// An error caused by an expression being nested too deeply
+    pub fn too_deep(location: Span) -> Self {
+        Self {
+            kind: ErrorKind::TooDeep,
+            message: Cow::Borrowed("expression is nested too deeply"),
+            location,
+        }
+    }
+
+    pub fn is_capacity_exceeded(&self) -> bool { self.kind == ErrorKind::CapacityExceeded }
+}
@@ -1,50 +1,288 @@
-use core::fmt;
+use core::fmt::{self, Write};
+use alloc::{borrow::Cow, string::String};
 use crate::*;
 
+/// LaTeX with only the brackets that are needed to read it correctly
 pub struct LatexDisplay<'a, T> {
     pub node: &'a Node<T>,
+    /// The source the node was parsed from. Numbers are written as they appear in it, unless their
+    /// span is empty because they were built or computed.
     pub src: &'a str,
 }
 
-impl<T> fmt::Display for LatexDisplay<'_, T> {
+impl<T: fmt::Display + NumberForm> fmt::Display for LatexDisplay<'_, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.node.kind {
-            NodeKind::Number(_) => write!(f, "{}", &self.src[self.node.span.clone()]),
-            NodeKind::BiOp(l, BiOpr::Divide, r) => write!(f, r"\left(\frac{{{}}}{{{}}}\right)", LatexDisplay {
-                node: l,
-                src: self.src,
-            }, LatexDisplay {
-                node: r,
-                src: self.src,
-            }),
-            NodeKind::BiOp(l, o, r) => write!(f, r"\left({{{}}}{o}{{{}}}\right)", LatexDisplay {
-                node: l,
-                src: self.src,
-            }, LatexDisplay {
-                node: r,
-                src: self.src,
-            }),
-            NodeKind::UnOp(op, o) => write!(f, r"\left({op} {}\right)", LatexDisplay {
-                node: o,
-                src: self.src,
-            }),
-            NodeKind::Function(n, a) => {
-                write!(f, r"\text{{{n}}}\left(")?;
+        let mut s = String::new();
+        self.write(self.node, &mut s);
+        f.write_str(&s)
+    }
+}
+
+impl<T: fmt::Display + NumberForm> LatexDisplay<'_, T> {
+    fn write(&self, node: &Node<T>, s: &mut String) {
+        match &node.kind {
+            NodeKind::Number(n) => number_text(&number(n, &node.span, self.src), s),
+            NodeKind::Constant(c) => s.push_str(constant(c)),
+            NodeKind::Group(_, v) => self.write(v, s),
+            NodeKind::UnOp(op, v) => {
+                let _ = write!(s, "{op}");
+                self.operand(v, needs_brackets(node, Side::Right), s);
+            },
+            NodeKind::BiOp(l, BiOpr::Divide, r) => {
+                s.push_str(r"\frac{");
+                self.write(l, s);
+                s.push_str("}{");
+                self.write(r, s);
+                s.push('}');
+            },
+            NodeKind::BiOp(l, BiOpr::Power, r) => {
+                self.operand(l, needs_brackets(node, Side::Left), s);
+                s.push_str("^{");
+                self.write(r, s);
+                s.push('}');
+            },
+            NodeKind::BiOp(l, op, r) => {
+                self.operand(l, needs_brackets(node, Side::Left), s);
+
+                let mut right = String::new();
+                self.operand(r, needs_brackets(node, Side::Right), &mut right);
+
+                if *op != BiOpr::ImplicitMultiply {
+                    let _ = write!(s, " {op} ");
+                } else if right.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+                    s.push_str(r" \cdot ");
+                } else if s.ends_with(|c: char| c.is_ascii_alphabetic()) && right.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    s.push(' ');
+                }
+
+                s.push_str(&right);
+            },
+            NodeKind::Function(c) => self.function(c, s),
+        }
+    }
+
+    fn function(&self, c: &Call<T>, s: &mut String) {
+        let a = &c.args;
+
+        match (c.name.as_str(), a.len()) {
+            ("sqrt" | "√", 1) => self.wrap(r"\sqrt{", &a[0], "}", s),
+            ("cbrt" | "∛", 1) => self.wrap(r"\sqrt[3]{", &a[0], "}", s),
+            ("abs", 1) => self.wrap(r"\left|", &a[0], r"\right|", s),
+            ("floor", 1) => self.wrap(r"\left\lfloor ", &a[0], r" \right\rfloor", s),
+            ("ceil", 1) => self.wrap(r"\left\lceil ", &a[0], r" \right\rceil", s),
+            ("conj", 1) => self.wrap(r"\overline{", &a[0], "}", s),
+            ("log", 2) => {
+                self.wrap(r"\log_{", &a[1], "}", s);
+                self.wrap(r"\left(", &a[0], r"\right)", s);
+            },
+            (name, _) => {
+                if FUNCTIONS.contains(&name) {
+                    let _ = write!(s, r"\{name}");
+                } else {
+                    let _ = write!(s, r"\operatorname{{{name}}}");
+                }
+
+                s.push_str(r"\left(");
 
                 for (i, r) in a.iter().enumerate() {
-                    fmt::Display::fmt(&LatexDisplay {
-                        node: r,
-                        src: self.src,
-                    }, f)?;
-                    if i != a.len() - 1 {
-                        write!(f, ",")?;
+                    if i != 0 {
+                        s.push_str(", ");
                     }
+
+                    self.write(r, s);
                 }
 
-                write!(f, r"\right)")
+                s.push_str(r"\right)");
             },
         }
     }
+
+    fn wrap(&self, open: &str, node: &Node<T>, close: &str, s: &mut String) {
+        s.push_str(open);
+        self.write(node, s);
+        s.push_str(close);
+    }
+
+    fn operand(&self, node: &Node<T>, brackets: bool, s: &mut String) {
+        if !brackets {
+            return self.write(node, s);
+        }
+
+        let (open, close) = match &node.kind {
+            NodeKind::Group(k, _) => brackets_of(*k),
+            _ => brackets_of(BKind::Round),
+        };
+
+        let _ = write!(s, r"\left{open}");
+        self.write(node, s);
+        let _ = write!(s, r"\right{close}");
+    }
+}
+
+/// Functions that LaTeX has a command for
+pub(crate) const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "sinh", "cosh", "tanh", "arcsin", "arccos", "arctan", "ln", "log", "exp", "max", "min",
+];
+
+/// A number as it appears in `src`, or as it is displayed if the node was not parsed from a source
+pub(crate) fn number<'a, T: fmt::Display>(n: &T, span: &Span, src: &'a str) -> Cow<'a, str> {
+    match src.get(span.clone()) {
+        Some(s) if !span.is_empty() => Cow::Borrowed(s.trim()),
+        _ => Cow::Owned(alloc::format!("{n}")),
+    }
+}
+
+/// Where the text of a number splits at the top level, into a sum such as `1 + (1 / 2)i` and into a
+/// fraction such as `-1 / 2`
+pub(crate) fn number_parts(t: &str) -> (Option<usize>, Option<usize>) {
+    let mut depth = 0;
+    let mut sum = None;
+    let mut fraction = None;
+
+    for (i, c) in t.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-' if depth == 0 && i != 0 && !t[..i].ends_with(['e', 'E']) => sum = Some(i),
+            '/' if depth == 0 => fraction = Some(i),
+            _ => {},
+        }
+    }
+
+    (sum, fraction)
+}
+
+/// The text of a number with its fractions as `\frac`s
+fn number_text(text: &str, s: &mut String) {
+    let t = text.trim();
+    let (sum, fraction) = number_parts(t);
+
+    if let Some(i) = sum {
+        number_text(&t[..i], s);
+        s.push_str(if t[i..].starts_with('+') { " + " } else { " - " });
+        number_text(&t[i + 1..], s);
+    } else if let Some(v) = t.strip_prefix('-') {
+        s.push('-');
+        number_text(v, s);
+    } else if let Some(i) = fraction {
+        s.push_str(r"\frac{");
+        number_text(&t[..i], s);
+        s.push_str("}{");
+        number_text(&t[i + 1..], s);
+        s.push('}');
+    } else if let Some(v) = t.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        // a negative part keeps its brackets, e.g. `2 + (-1 / 2)i`
+        if v.starts_with('-') {
+            s.push_str(r"\left(");
+            number_text(v, s);
+            s.push_str(r"\right)");
+        } else {
+            number_text(v, s);
+        }
+    } else if let Some(v) = t.strip_suffix('i').filter(|v| !v.is_empty()) {
+        number_text(v, s);
+        s.push('i');
+    } else if t == "inf" {
+        s.push_str(r"\infty");
+    } else {
+        s.push_str(t);
+    }
+}
+
+/// The node without any brackets around it
+pub(crate) fn unwrap<T>(node: &Node<T>) -> &Node<T> {
+    match &node.kind {
+        NodeKind::Group(_, v) => unwrap(v),
+        _ => node,
+    }
+}
+
+/// How tightly the rendered node holds together, operands that hold together less tightly than
+/// their operation need brackets
+pub(crate) fn strength<T: NumberForm>(node: &Node<T>) -> u8 {
+    match &unwrap(node).kind {
+        NodeKind::BiOp(_, BiOpr::Add | BiOpr::Subtract, _) => 1,
+        NodeKind::Number(n) if n.is_sum() => 1,
+        NodeKind::BiOp(_, BiOpr::Multiply | BiOpr::ImplicitMultiply | BiOpr::Modulo, _) | NodeKind::UnOp(..) => 2,
+        NodeKind::BiOp(_, BiOpr::Power, _) => 3,
+        _ => 4,
+    }
+}
+
+/// An operand of an operation, the operand of a unary operation is on the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Side {
+    Left,
+    Right,
+}
+
+/// Whether the operand on `side` of `node` needs brackets to be read correctly. Numerators,
+/// denominators and exponents never do, the layout sets them apart. Negative numbers are operands
+/// like unary operations, e.g. `(-2)^2`, and complex numbers such as `1 + 2i` like sums, as told
+/// by their [`NumberForm`].
+pub(crate) fn needs_brackets<T: NumberForm>(node: &Node<T>, side: Side) -> bool {
+    let unary = |n: &Node<T>| match &unwrap(n).kind {
+        NodeKind::UnOp(..) => true,
+        NodeKind::Number(n) => n.is_negative(),
+        _ => false,
+    };
+
+    match (&unwrap(node).kind, side) {
+        (NodeKind::UnOp(_, v), _) => strength(v) < 2 || unary(v),
+        (NodeKind::BiOp(l, BiOpr::Power, _), Side::Left) => {
+            matches!(unwrap(l).kind, NodeKind::BiOp(..)) || unary(l) || strength(l) < 2
+        },
+        (NodeKind::BiOp(_, BiOpr::Power | BiOpr::Divide, _), _) => false,
+        (NodeKind::BiOp(l, ..), Side::Left) => strength(l) < strength(node),
+        (NodeKind::BiOp(.., r), Side::Right) => {
+            let p = strength(node);
+            let same = strength(r) == p && !matches!(unwrap(r).kind, NodeKind::BiOp(_, BiOpr::Divide, _));
+
+            unary(r) || strength(r) < p || same
+        },
+        _ => false,
+    }
+}
+
+fn brackets_of(k: BKind) -> (&'static str, &'static str) {
+    match k {
+        BKind::Round => ("(", ")"),
+        BKind::Square => ("[", "]"),
+        BKind::Curly => (r"\{", r"\}"),
+    }
+}
+
+/// The LaTeX for a constant
+pub fn constant(c: &str) -> &str {
+    match c {
+        "π" => r"\pi",
+        "φ" => r"\phi",
+        "ϕ" => r"\varphi",
+        "τ" => r"\tau",
+        "γ" => r"\gamma",
+        "c_m/s" => r"c",
+        "∞" => r"\infty",
+        "↉" => r"\frac{0}{3}",
+        "½" => r"\frac{1}{2}",
+        "⅓" => r"\frac{1}{3}",
+        "¼" => r"\frac{1}{4}",
+        "⅕" => r"\frac{1}{5}",
+        "⅙" => r"\frac{1}{6}",
+        "⅐" => r"\frac{1}{7}",
+        "⅛" => r"\frac{1}{8}",
+        "⅑" => r"\frac{1}{9}",
+        "⅒" => r"\frac{1}{10}",
+        "⅔" => r"\frac{2}{3}",
+        "⅖" => r"\frac{2}{5}",
+        "¾" => r"\frac{3}{4}",
+        "⅗" => r"\frac{3}{5}",
+        "⅜" => r"\frac{3}{8}",
+        "⅘" => r"\frac{4}{5}",
+        "⅚" => r"\frac{5}{6}",
+        "⅝" => r"\frac{5}{8}",
+        "⅞" => r"\frac{7}{8}",
+        _ => c,
+    }
 }
 
 impl fmt::Display for BiOpr {
@@ -52,7 +290,8 @@ impl fmt::Display for BiOpr {
         write!(f, "{}", match self {
             Self::Add => "+",
             Self::Subtract => "-",
-            Self::Multiply => r"\times",
+            Self::Multiply => r"\cdot",
+            Self::ImplicitMultiply => "",
             Self::Divide => r"\div",
             Self::Modulo => r"\bmod",
             Self::Power => r"^",
@@ -68,3 +307,118 @@ impl fmt::Display for UnOpr {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn latex(s: &str) -> alloc::string::String {
+        let node = crate::Expr::parse(s).unwrap();
+        LatexDisplay { node: &node, src: s }.to_string()
+    }
+
+    #[test]
+    fn snapshots() {
+        for (s, expected) in [
+            ("1+2*3", r"1 + 2 \cdot 3"),
+            ("(1+2)*3", r"\left(1 + 2\right) \cdot 3"),
+            ("[1+2]*3", r"\left[1 + 2\right] \cdot 3"),
+            ("1-(2-3)", r"1 - \left(2 - 3\right)"),
+            ("(1-2)-3", r"1 - 2 - 3"),
+            ("1/(2+3)", r"\frac{1}{2 + 3}"),
+            ("(1/2)^2", r"\left(\frac{1}{2}\right)^{2}"),
+            ("2^(3+4)", r"2^{3 + 4}"),
+            ("2^3^2", r"2^{3^{2}}"),
+            ("(2^3)^2", r"\left(2^{3}\right)^{2}"),
+            ("-2^2", r"\left(-2\right)^{2}"),
+            ("-(2^2)", r"-2^{2}"),
+            ("-(1+2)", r"-\left(1 + 2\right)"),
+            ("2*-3", r"2 \cdot \left(-3\right)"),
+            ("2π", r"2\pi"),
+            ("πe", r"\pi e"),
+            ("2(3)", r"2 \cdot 3"),
+            ("2(1+π)", r"2\left(1 + \pi\right)"),
+            ("5%3", r"5 \bmod 3"),
+            ("sin(π/2)", r"\sin\left(\frac{\pi}{2}\right)"),
+            ("arcsinh(1)", r"\operatorname{arcsinh}\left(1\right)"),
+            ("sqrt(2)cbrt[3]", r"\sqrt{2}\sqrt[3]{3}"),
+            ("√(2)^2", r"\sqrt{2}^{2}"),
+            ("log(8, 2)", r"\log_{2}\left(8\right)"),
+            ("abs(-1) + floor(½)", r"\left|-1\right| + \left\lfloor \frac{1}{2} \right\rfloor"),
+            ("max(1, 2, 3)", r"\max\left(1, 2, 3\right)"),
+        ] {
+            assert_eq!(latex(s), expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn built() {
+        use crate::build::*;
+
+        for (n, expected) in [
+            (num::<f64>(-2.0).pow(num(2.0)), r"\left(-2\right)^{2}"),
+            (num(2.0) * num(-3.0), r"2 \cdot \left(-3\right)"),
+            (-num(-1.5), r"-\left(-1.5\right)"),
+            (num(-2.0) * var("π"), r"-2 \cdot \pi"),
+        ] {
+            assert_eq!(LatexDisplay { node: &n, src: "" }.to_string(), expected);
+        }
+
+        let n = |s: &str| -> Node<Literal> { num(Literal(s.into())) };
+        for (n, expected) in [
+            (n("(19 / 30)"), r"\frac{19}{30}"),
+            (n("(-1 / 2)"), r"\left(-\frac{1}{2}\right)"),
+            (n("1 + (1 / 2)i") * var("x"), r"\left(1 + \frac{1}{2}i\right) \cdot x"),
+            (n("-inf"), r"-\infty"),
+        ] {
+            assert_eq!(LatexDisplay { node: &n, src: "" }.to_string(), expected);
+        }
+    }
+
+    #[cfg(feature = "any_num")]
+    fn eval(s: &str, dialect: Dialect) -> f64 { to_nodes_with::<f64>(s, dialect).unwrap().evaluate().unwrap() }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn input() {
+        for (s, expected) in [
+            (r"\frac{1}{2}", 0.5),
+            (r"\dfrac{1+2}{4}", 0.75),
+            (r"\sqrt{16} + \sqrt[3]{27} + \sqrt[4]{16}", 9.0),
+            (r"2 \cdot 3 \times 4 \div 8", 3.0),
+            (r"\left( 1 + 2 \right) \cdot 3", 9.0),
+            (r"\left\{1 + 2\right\}^{2}", 9.0),
+            (r"\sin(\frac{\pi}{2}) + \sin \frac{\pi}{2} + \cos\left(0\right)", 3.0),
+            (r"2\pi - \tau", 0.0),
+            (r"\ln{1} + \operatorname{max}(1, 2) + 7 \bmod 4", 5.0),
+            (r"2\,\sqrt{4}", 4.0),
+            (r"\frac12 + \frac\pi\pi + \frac 1 2", 2.0),
+            (r"\sqrt4 + \sqrt 49", 20.0),
+        ] {
+            assert!((eval(s, Dialect::Latex) - expected).abs() < 1e-12, "{s}");
+        }
+
+        let err = |s| to_nodes_with::<f64>(s, Dialect::Latex).unwrap_err();
+        assert_eq!(err(r"1 + \foo").location, 4..8);
+        assert_eq!(eval(r"\frac12", Dialect::Latex), 0.5);
+        assert_eq!(err(r"\frac+1").message, "expected `{`");
+
+        let n = to_nodes_with_variables_in::<f64>(r"\frac{xy}{2} + \sqrt{x}", Dialect::Latex).unwrap();
+        assert_eq!(n.to_string(), "x y / 2 + sqrt(x)");
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn rendered_input() {
+        for s in [
+            "1+2*3", "(1/2)^2", "2^3^2", "-(2^2)", "-2^2", "2*-3", "2π", "sin(π/2)", "sqrt(2)cbrt[3]", "5%3",
+            "max(1, 2, 3)", "1-(2-3)", "2(1+π)", "2^(1/2)",
+        ] {
+            let rendered = latex(s);
+            let v = eval(&rendered, Dialect::Latex);
+            assert!((v - eval(s, Dialect::Plain)).abs() < 1e-12, "{s} was rendered as {rendered}");
+        }
+    }
+}
+
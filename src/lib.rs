@@ -1,6 +1,9 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 extern crate alloc;
 
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("smolcalc needs the `std` feature, or the `libm` feature for `no_std` targets");
+
 pub mod traits;
 
 #[cfg(feature = "num_rational")]
@@ -8,32 +11,128 @@ pub mod rational;
 
 pub mod error;
 pub use error::*;
+#[cfg(feature = "serde")]
+pub mod schema;
 pub mod latex;
-
-use alloc::{boxed::Box, string::ToString};
+pub mod mathml;
+pub mod pretty;
+pub mod speech;
+mod asciimath;
+pub mod text;
+pub mod arena;
+pub mod visit;
+pub mod build;
+pub mod partial;
+pub use partial::Context;
+#[cfg(feature = "any_num")]
+pub mod simplify;
+pub mod rewrite;
+pub use arena::{Arena, ArenaKind, ArenaNode, NodeId, SideTable};
+
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec,
+};
+use core::hash::{Hash, Hasher};
 use traits::*;
 
 /// A range in bytes
 pub type Span = core::ops::Range<usize>;
 
-type PeekingLexer<'src, Number> = Peeking<Lexer<'src, Number>, Result<Token<Number>, Error>>;
+pub fn to_nodes<T: Clone + Numeral>(s: &str) -> Result<Node<T>, Error> { to_nodes_with(s, Dialect::Plain) }
 
-pub fn to_nodes<T: Clone + Numeral>(s: &str) -> Result<Node<T>, Error> {
-    let lex = Lexer::<T> {
-        source: s.chars(),
-        start_index: 0,
-        current_idx: 0,
-        skipped: None,
+pub fn to_nodes_with<T: Clone + Numeral>(s: &str, dialect: Dialect) -> Result<Node<T>, Error> {
+    let (arena, root) = parse_with(s, dialect)?;
+    arena.to_node(root)
+}
 
-        _num: core::marker::PhantomData::default(),
-    };
-    let mut lex = Peeking::from_iter(lex);
+/// Parses `s` with unknown names as variables, e.g. for [`partial`]
+pub fn to_nodes_with_variables<T: Clone + Numeral>(s: &str) -> Result<Node<T>, Error> {
+    to_nodes_with_variables_in(s, Dialect::Plain)
+}
+
+/// Parses `s` in `dialect` with unknown names as variables, e.g. `sqrt x` in ASCIIMath
+pub fn to_nodes_with_variables_in<T: Clone + Numeral>(s: &str, dialect: Dialect) -> Result<Node<T>, Error> {
+    let mut arena = Arena::growable();
+    let root = arena.parse_with_variables_in(s, dialect)?;
+    arena.to_node(root)
+}
+
+/// Parses `s` into a heap-allocated [`Arena`] and returns it with the root node
+pub fn parse(s: &str) -> Result<(Arena<'_, '_>, NodeId), Error> { parse_with(s, Dialect::Plain) }
+
+pub fn parse_with(s: &str, dialect: Dialect) -> Result<(Arena<'_, '_>, NodeId), Error> {
+    let mut arena = Arena::growable();
+    let root = arena.parse_with(s, dialect)?;
+
+    Ok((arena, root))
+}
+
+/// The syntax of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    #[default]
+    Plain,
+    /// LaTeX math, e.g. `\frac{1}{2} \cdot \sqrt[3]{8}`
+    Latex,
+    /// ASCIIMath, e.g. `root(3)(8) xx sqrt 2`
+    AsciiMath,
+}
+
+/// The kind of a token returned by [`tokenize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Number,
+    Operator,
+    OpenBracket,
+    CloseBracket,
+    Function,
+    Constant,
+    Comma,
+    /// Input that could not be made into a token
+    Invalid,
+}
+
+/// Splits `s` into tokens, e.g. for syntax highlighting. Whitespace is skipped and the bracket
+/// after a function name is a token of its own.
+///
+/// This never fails, input that is not understood becomes [`TokenKind::Invalid`] tokens.
+pub fn tokenize(s: &str) -> impl Iterator<Item = (TokenKind, Span)> + '_ {
+    Lexer::new(s, Dialect::Plain).flat_map(|t| {
+        let (first, second) = match t {
+            Ok((Token::FStart(..), span)) => {
+                let b = span.end - 1;
+                ((TokenKind::Function, span.start..b), Some((TokenKind::OpenBracket, b..span.end)))
+            },
+            Ok((t, span)) => {
+                let kind = match t {
+                    Token::Operator(_) => TokenKind::Operator,
+                    Token::Number(_) => TokenKind::Number,
+                    Token::Constant(_) => TokenKind::Constant,
+                    Token::BStart(_) => TokenKind::OpenBracket,
+                    Token::BEnd(_) => TokenKind::CloseBracket,
+                    Token::Comma => TokenKind::Comma,
+                    Token::Frac | Token::Sqrt | Token::Root | Token::Command(_) => TokenKind::Function,
+                    Token::FStart(..) => unreachable!(),
+                };
+
+                ((kind, span), None)
+            },
+            Err(e) => ((TokenKind::Invalid, e.location), None),
+        };
+
+        core::iter::once(first).chain(second)
+    })
+}
 
-    let e = parse_expr_climb(&mut lex, 0)?;
+fn parse_expr<'src>(lex: &mut Tokens<'src>, arena: &mut Arena<'_, 'src>) -> Result<NodeId, Error> {
+    let e = parse_expr_climb(lex, arena, 0)?;
 
     if lex.next().is_some() {
         return Err(Error {
-            message: "expected end of expression",
+            kind: ErrorKind::Syntax,
+            message: "expected end of expression".into(),
             location: lex.report_span(),
         });
     }
@@ -41,15 +140,15 @@ pub fn to_nodes<T: Clone + Numeral>(s: &str) -> Result<Node<T>, Error> {
     Ok(e)
 }
 
-fn parse_expr_climb<T: Clone + Numeral>(
-    lex: &mut PeekingLexer<'_, T>,
+fn parse_expr_climb<'src>(
+    lex: &mut Tokens<'src>,
+    arena: &mut Arena<'_, 'src>,
     percedence: usize,
-) -> Result<Node<T>, Error> {
-    let mut rest = parse_single(lex)?;
-    let mut depth = 0;
+) -> Result<NodeId, Error> {
+    let mut rest = parse_single(lex, arena)?;
 
     loop {
-        match lex.peek() {
+        let op = match lex.peek() {
             Some(Ok(Token::Operator(op))) if op.binary().is_some() => {
                 let op = op.binary().unwrap();
 
@@ -58,176 +157,447 @@ fn parse_expr_climb<T: Clone + Numeral>(
                 }
 
                 lex.next();
-
-                let rhs =
-                    parse_expr_climb(lex, op.percedence() + op.is_left_associative() as usize)?;
-
-                let rest_start = rest.span.start;
-                let rhs_end = rhs.span.end;
-
-                if op.is_left_associative() {
-                    rest = Node {
-                        kind: NodeKind::BiOp(Box::new(rest), op, Box::new(rhs)),
-                        span: rest_start..rhs_end,
-                    };
-                } else {
-                    add_node_right(&mut rest, depth, op, rhs);
-                }
+                op
             },
-            Some(Ok(Token::FStart(..) | Token::BStart(..) | Token::Number(..)))
-                if BiOpr::Multiply.percedence() >= percedence =>
+            Some(Ok(
+                Token::FStart(..)
+                | Token::BStart(..)
+                | Token::Number(..)
+                | Token::Constant(..)
+                | Token::Frac
+                | Token::Sqrt
+                | Token::Root
+                | Token::Command(_),
+            )) if BiOpr::ImplicitMultiply.percedence() >= percedence =>
             {
-                let rhs = parse_expr_climb(
-                    lex,
-                    BiOpr::Multiply.percedence() + BiOpr::Multiply.is_left_associative() as usize,
-                )?;
-
-                let rest_start = rest.span.start;
-                let rhs_end = rhs.span.end;
-
-                rest = Node {
-                    kind: NodeKind::BiOp(Box::new(rest), BiOpr::Multiply, Box::new(rhs)),
-                    span: rest_start..rhs_end,
-                };
+                BiOpr::ImplicitMultiply
             },
             _ => break,
-        }
+        };
+
+        lex.enter()?;
+        let rhs = parse_expr_climb(lex, arena, op.percedence() + op.is_left_associative() as usize)?;
+        lex.leave();
+        let span = arena[rest].span.start..arena[rhs].span.end;
 
-        depth += 1;
+        rest = arena.push(ArenaKind::BiOp(rest, op, rhs), span)?;
     }
 
     Ok(rest)
 }
 
-fn add_node_right<T: Clone>(rest: &mut Node<T>, depth: usize, op: BiOpr, right: Node<T>) {
-    let rse = right.span.end;
-
-    if depth == 0 {
-        *rest = Node {
-            span: rest.span.start..right.span.end,
-            kind: NodeKind::BiOp(Box::new(rest.clone()), op, Box::new(right)),
-        };
+fn parse_group<'src>(
+    lex: &mut Tokens<'src>,
+    arena: &mut Arena<'_, 'src>,
+    k: BKind,
+    start: usize,
+) -> Result<NodeId, Error> {
+    let inner = parse_expr_climb(lex, arena, 0)?;
+    if let Some(Ok(Token::BEnd(ke))) = lex.next() {
+        if k == ke {
+            arena.push(ArenaKind::Group(k, inner), start..lex.report_span().end)
+        } else {
+            Err(Error {
+                kind: ErrorKind::Syntax,
+                message: "bracket type mismatch".into(),
+                location: lex.report_span(),
+            })
+        }
+    } else {
+        Err(Error {
+            kind: ErrorKind::Syntax,
+            message: "expected bracket end".into(),
+            location: lex.report_span(),
+        })
+    }
+}
 
-        return;
+/// Parses an argument of `\frac` or `frac`, which is in braces in LaTeX
+fn parse_argument<'src>(lex: &mut Tokens<'src>, arena: &mut Arena<'_, 'src>) -> Result<NodeId, Error> {
+    match lex.lexer.dialect {
+        Dialect::Latex => parse_braced(lex, arena),
+        _ => parse_single(lex, arena),
     }
+}
 
-    match &mut rest.kind {
-        NodeKind::BiOp(_, op2, r) if op == *op2 => {
-            add_node_right(r, depth - 1, op, right);
-        },
-        _ => {
-            *rest = Node {
-                span: rest.span.start..right.span.end,
-                kind: NodeKind::BiOp(Box::new(rest.clone()), op, Box::new(right)),
-            };
+/// Parses an argument of a LaTeX command such as `\frac`, which is in braces unless it is a single
+/// digit or constant, e.g. `\frac12` is `\frac{1}{2}`
+fn parse_braced<'src>(lex: &mut Tokens<'src>, arena: &mut Arena<'_, 'src>) -> Result<NodeId, Error> {
+    match lex.next() {
+        Some(Ok(Token::BStart(BKind::Curly))) => parse_group(lex, arena, BKind::Curly, lex.report_span().start),
+        Some(Ok(Token::Number(n))) => {
+            let start = lex.report_span().start;
+            let len = n.chars().next().map_or(0, char::len_utf8);
+            lex.rewind(start + len);
+
+            arena.push(ArenaKind::Number(&n[..len]), start..start + len)
         },
+        Some(Ok(Token::Constant(c))) => arena.push(ArenaKind::Constant(c), lex.report_span()),
+        _ => Err(Error {
+            kind: ErrorKind::Syntax,
+            message: "expected `{`".into(),
+            location: lex.report_span(),
+        }),
+    }
+}
+
+fn parse_call<'src>(
+    lex: &mut Tokens<'src>,
+    arena: &mut Arena<'_, 'src>,
+    f: &'src str,
+    k: BKind,
+    start: usize,
+    name_end: usize,
+) -> Result<NodeId, Error> {
+    let mut first = None;
+    let mut last: Option<NodeId> = None;
+
+    while let Some(t) = lex.peek() {
+        match t {
+            Ok(Token::BEnd(_)) => break,
+            _ => {
+                let arg = parse_expr_climb(lex, arena, 0)?;
+
+                match last {
+                    Some(last) => arena.set_next(last, arg),
+                    None => first = Some(arg),
+                }
+
+                last = Some(arg);
+            },
+        }
+
+        match lex.peek() {
+            Some(Ok(Token::BEnd(_))) => break,
+            Some(Ok(Token::Comma)) => {
+                lex.next();
+            },
+            _ => {
+                lex.next();
+                return Err(Error {
+                    kind: ErrorKind::Syntax,
+                    message: "expected comma or bracket end".into(),
+                    location: lex.report_span(),
+                });
+            },
+        }
+    }
+
+    if let Some(Ok(Token::BEnd(ke))) = lex.next() {
+        if k == ke {
+            arena.push(ArenaKind::Function(f, k, name_end, first), start..lex.report_span().end)
+        } else {
+            Err(Error {
+                kind: ErrorKind::Syntax,
+                message: "bracket type mismatch".into(),
+                location: lex.report_span(),
+            })
+        }
+    } else {
+        Err(Error {
+            kind: ErrorKind::Syntax,
+            message: "expected bracket end".into(),
+            location: lex.report_span(),
+        })
     }
+}
+
+fn parse_single<'src>(lex: &mut Tokens<'src>, arena: &mut Arena<'_, 'src>) -> Result<NodeId, Error> {
+    lex.enter()?;
+    let n = parse_nested(lex, arena);
+    lex.leave();
 
-    rest.span.end = rse;
+    n
 }
 
-fn parse_single<T: Clone + Numeral>(lex: &mut PeekingLexer<'_, T>) -> Result<Node<T>, Error> {
+/// Parses a number, constant, group, call or an operator with its operand
+fn parse_nested<'src>(lex: &mut Tokens<'src>, arena: &mut Arena<'_, 'src>) -> Result<NodeId, Error> {
     let t = lex.next();
 
     match t.ok_or_else(|| Error {
-        message: "unexpected end of expression",
+        kind: ErrorKind::Syntax,
+        message: "unexpected end of expression".into(),
         location: lex.report_span(),
     })?? {
-        Token::Number(num) => Ok(Node {
-            kind: NodeKind::Number(num),
-            span: lex.report_span(),
-        }),
-        Token::BStart(k) => {
-            let inner = parse_expr_climb(lex, 0)?;
-            if let Some(Ok(Token::BEnd(ke))) = lex.next() {
-                if k == ke {
-                    Ok(inner)
-                } else {
-                    Err(Error {
-                        message: "bracket type mismatch",
-                        location: lex.report_span(),
-                    })
-                }
-            } else {
-                Err(Error {
-                    message: "expected bracket end",
-                    location: lex.report_span(),
-                })
+        Token::Number(num) => arena.push(ArenaKind::Number(num), lex.report_span()),
+        Token::Constant(c) => arena.push(ArenaKind::Constant(c), lex.report_span()),
+        Token::BStart(k) => parse_group(lex, arena, k, lex.report_span().start),
+        Token::FStart(f, k) => {
+            let span = lex.report_span();
+            parse_call(lex, arena, f, k, span.start, span.end - 1)
+        },
+        Token::Command(f) => {
+            let span = lex.report_span();
+
+            match lex.peek() {
+                Some(Ok(Token::BStart(k))) => {
+                    let k = *k;
+                    lex.next();
+                    parse_call(lex, arena, f, k, span.start, span.end)
+                },
+                _ => {
+                    // e.g. `\sin 2x` in LaTeX, but `sin 2x` is `sin(2)x` in ASCIIMath
+                    let percedence = match lex.lexer.dialect {
+                        Dialect::AsciiMath => BiOpr::Power.percedence(),
+                        _ => BiOpr::Multiply.percedence(),
+                    };
+                    let arg = parse_expr_climb(lex, arena, percedence)?;
+                    let end = arena[arg].span.end;
+                    arena.push(ArenaKind::Function(f, BKind::Round, span.end, Some(arg)), span.start..end)
+                },
             }
         },
-        Token::FStart(f, k) => {
-            let sbs = lex.report_span();
-            let mut args = Vec::new();
+        Token::Frac => {
+            let start = lex.report_span().start;
+            let n = parse_argument(lex, arena)?;
+            let d = parse_argument(lex, arena)?;
+            let end = arena[d].span.end;
 
-            while let Some(t) = lex.peek() {
-                match t {
-                    Ok(Token::BEnd(_)) => break,
-                    _ => args.push(parse_expr_climb(lex, 0)?),
-                }
+            arena.push(ArenaKind::BiOp(n, BiOpr::Divide, d), start..end)
+        },
+        Token::Sqrt => {
+            let start = lex.report_span().start;
+            let index = match lex.peek() {
+                Some(Ok(Token::BStart(BKind::Square))) => {
+                    lex.next();
+                    Some(parse_group(lex, arena, BKind::Square, lex.report_span().start)?)
+                },
+                _ => None,
+            };
+            let radicand = parse_braced(lex, arena)?;
 
-                match lex.peek() {
-                    Some(Ok(Token::BEnd(_))) => break,
-                    Some(Ok(Token::Comma)) => {
-                        lex.next();
-                    },
-                    _ => {
-                        lex.next();
-                        return Err(Error {
-                            message: "expected comma or bracket end",
-                            location: lex.report_span(),
-                        });
-                    },
-                }
-            }
+            push_root(arena, index, radicand, start)
+        },
+        Token::Root => {
+            let start = lex.report_span().start;
+            let index = parse_single(lex, arena)?;
+            let radicand = parse_single(lex, arena)?;
 
-            if let Some(Ok(Token::BEnd(ke))) = lex.next() {
-                if k == ke {
-                    Ok(Node {
-                        kind: NodeKind::Function(f, args),
-                        span: sbs.start..lex.report_span().end,
-                    })
-                } else {
-                    Err(Error {
-                        message: "bracket type mismatch",
-                        location: lex.report_span(),
-                    })
-                }
-            } else {
-                Err(Error {
-                    message: "expected bracket end",
-                    location: lex.report_span(),
-                })
-            }
+            push_root(arena, Some(index), radicand, start)
         },
         Token::Operator(op) if op.unary().is_some() => {
             let op_span = lex.report_span();
             let op = op.unary().unwrap();
-            let expr = parse_expr_climb(lex, op.percedence())?;
-            let expr_end = expr.span.end;
-            Ok(Node {
-                kind: NodeKind::UnOp(op, Box::new(expr)),
-                span: op_span.start..expr_end,
-            })
+            // in LaTeX and ASCIIMath `-2^2` is `-(2^2)` like it is read in writing
+            let percedence = match lex.lexer.dialect {
+                Dialect::Plain => op.percedence(),
+                Dialect::Latex | Dialect::AsciiMath => BiOpr::Power.percedence(),
+            };
+            let expr = parse_expr_climb(lex, arena, percedence)?;
+            let span = op_span.start..arena[expr].span.end;
+
+            arena.push(ArenaKind::UnOp(op, expr), span)
         },
         _ => Err(Error {
-            message: "did not expect this",
+            kind: ErrorKind::Syntax,
+            message: "did not expect this".into(),
             location: lex.report_span(),
         }),
     }
 }
 
+/// Pushes the `index`th root of `radicand`, as `sqrt` or `cbrt` if it can be written like that
+fn push_root(arena: &mut Arena<'_, '_>, index: Option<NodeId>, radicand: NodeId, start: usize) -> Result<NodeId, Error> {
+    let (k, arg) = match arena[radicand].kind {
+        ArenaKind::Group(k, arg) => (k, arg),
+        _ => (BKind::Round, radicand),
+    };
+    let name_end = arena[radicand].span.start;
+    let end = arena[radicand].span.end;
+
+    let index = index.map(|i| match arena[i].kind {
+        ArenaKind::Group(_, i) => i,
+        _ => i,
+    });
+
+    match index.map(|i| arena[i].kind) {
+        None | Some(ArenaKind::Number("2")) => {
+            arena.push(ArenaKind::Function("sqrt", k, name_end, Some(arg)), start..end)
+        },
+        Some(ArenaKind::Number("3")) => arena.push(ArenaKind::Function("cbrt", k, name_end, Some(arg)), start..end),
+        Some(_) => {
+            let index = index.unwrap();
+            let span = arena[index].span.clone();
+            let one = arena.push(ArenaKind::Number("1"), span.clone())?;
+            let exp = arena.push(ArenaKind::BiOp(one, BiOpr::Divide, index), span)?;
+
+            arena.push(ArenaKind::BiOp(radicand, BiOpr::Power, exp), start..end)
+        },
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<Number> {
     pub kind: NodeKind<Number>,
     pub span: Span,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeKind<Number> {
     Number(Number),
+    /// A named constant such as `π`, resolved by [`FromConstant`] when evaluated
+    Constant(String),
     BiOp(Box<Node<Number>>, BiOpr, Box<Node<Number>>),
     UnOp(UnOpr, Box<Node<Number>>),
-    Function(String, Vec<Node<Number>>),
+    Function(Call<Number>),
+    /// An expression in brackets
+    Group(BKind, Box<Node<Number>>),
+}
+
+/// A function call such as `max(1, 2)`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Call<Number> {
+    pub name: String,
+    /// Where the name is in the source
+    pub name_span: Span,
+    pub bracket: BKind,
+    /// Where the brackets and the arguments are in the source
+    pub args_span: Span,
+    pub args: Vec<Node<Number>>,
+}
+
+/// A number as it was written in the source, e.g. `0.1`, not yet converted to any number type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Literal(pub String);
+
+impl Literal {
+    /// Converts the literal to a number of type `T`, `span` is used for the error
+    pub fn to_number<T: Numeral>(&self, span: &Span) -> Result<T, Error> { arena::parse_number(&self.0, span) }
+}
+
+impl<N: NumberEq> Node<N> {
+    /// Like `==`, but the spans have to be the same too
+    pub fn eq_spanned(&self, other: &Self) -> bool { self.span == other.span && self.kind.eq_with(&other.kind, true) }
+}
+
+/// Trees are equal if they have the same shape, operators, brackets, names and numbers. Spans are
+/// not compared, see [`Node::eq_spanned`].
+impl<N: NumberEq> PartialEq for Node<N> {
+    fn eq(&self, other: &Self) -> bool { self.kind == other.kind }
+}
+
+impl<N: NumberEq> Eq for Node<N> {}
+
+impl<N: NumberEq> Hash for Node<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.kind.hash(state) }
+}
+
+impl<N: NumberEq> NodeKind<N> {
+    fn eq_with(&self, other: &Self, spans: bool) -> bool {
+        let eq = |a: &Node<N>, b: &Node<N>| if spans { a.eq_spanned(b) } else { a == b };
+
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.number_eq(b),
+            (Self::Constant(a), Self::Constant(b)) => a == b,
+            (Self::BiOp(al, ao, ar), Self::BiOp(bl, bo, br)) => ao == bo && eq(al, bl) && eq(ar, br),
+            (Self::UnOp(ao, a), Self::UnOp(bo, b)) => ao == bo && eq(a, b),
+            (Self::Function(a), Self::Function(b)) => {
+                a.name == b.name
+                    && a.bracket == b.bracket
+                    && (!spans || (a.name_span == b.name_span && a.args_span == b.args_span))
+                    && a.args.len() == b.args.len()
+                    && a.args.iter().zip(&b.args).all(|(a, b)| eq(a, b))
+            },
+            (Self::Group(ak, a), Self::Group(bk, b)) => ak == bk && eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl<N: NumberEq> PartialEq for NodeKind<N> {
+    fn eq(&self, other: &Self) -> bool { self.eq_with(other, false) }
+}
+
+impl<N: NumberEq> Eq for NodeKind<N> {}
+
+impl<N: NumberEq> Hash for NodeKind<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+
+        match self {
+            Self::Number(n) => n.number_hash(state),
+            Self::Constant(c) => c.hash(state),
+            Self::BiOp(l, op, r) => {
+                l.hash(state);
+                op.hash(state);
+                r.hash(state);
+            },
+            Self::UnOp(op, v) => {
+                op.hash(state);
+                v.hash(state);
+            },
+            Self::Function(c) => {
+                c.name.hash(state);
+                c.bracket.hash(state);
+                c.args.hash(state);
+            },
+            Self::Group(k, v) => {
+                k.hash(state);
+                v.hash(state);
+            },
+        }
+    }
+}
+
+/// An expression that is not tied to a number type, so it can be parsed once and evaluated as
+/// any number type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expr(pub Node<Literal>);
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self, Error> { Self::parse_with(s, Dialect::Plain) }
+
+    pub fn parse_with(s: &str, dialect: Dialect) -> Result<Self, Error> {
+        let (arena, root) = parse_with(s, dialect)?;
+        Ok(arena.to_expr(root))
+    }
+
+    /// Converts every literal to `T`
+    pub fn to_typed<T: Numeral>(&self) -> Result<Node<T>, Error> { self.0.try_map(&mut |l, span| l.to_number(span)) }
+
+    #[cfg(not(feature = "any_num"))]
+    pub fn evaluate(&self) -> Result<f32, Error> { self.0.evaluate_with(&|l, span| l.to_number(span)) }
+
+    #[cfg(feature = "any_num")]
+    pub fn evaluate<T: ComputableNumeral>(&self) -> Result<T, Error> {
+        self.0.evaluate_with(&|l, span| l.to_number(span))
+    }
+}
+
+impl core::ops::Deref for Expr {
+    type Target = Node<Literal>;
+
+    fn deref(&self) -> &Node<Literal> { &self.0 }
+}
+
+impl<N> Node<N> {
+    /// Builds a tree of the same shape with every number converted by `f`
+    pub fn try_map<M, E>(&self, f: &mut impl FnMut(&N, &Span) -> Result<M, E>) -> Result<Node<M>, E> {
+        let kind = match &self.kind {
+            NodeKind::Number(n) => NodeKind::Number(f(n, &self.span)?),
+            NodeKind::Constant(c) => NodeKind::Constant(c.clone()),
+            NodeKind::BiOp(l, op, r) => NodeKind::BiOp(Box::new(l.try_map(f)?), *op, Box::new(r.try_map(f)?)),
+            NodeKind::UnOp(op, v) => NodeKind::UnOp(*op, Box::new(v.try_map(f)?)),
+            NodeKind::Function(c) => NodeKind::Function(Call {
+                name: c.name.clone(),
+                name_span: c.name_span.clone(),
+                bracket: c.bracket,
+                args_span: c.args_span.clone(),
+                args: c.args.iter().map(|a| a.try_map(f)).collect::<Result<_, _>>()?,
+            }),
+            NodeKind::Group(k, v) => NodeKind::Group(*k, Box::new(v.try_map(f)?)),
+        };
+
+        Ok(Node {
+            kind,
+            span: self.span.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -240,17 +610,21 @@ enum OperatorRaw {
     Power,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BiOpr {
     Add,
     Subtract,
     Multiply,
+    /// A multiplication written without an operator, e.g. `2π`
+    ImplicitMultiply,
     Divide,
     Modulo,
     Power,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOpr {
     Plus,
     Minus,
@@ -281,7 +655,7 @@ impl BiOpr {
     fn percedence(self) -> usize {
         match self {
             Self::Power => 3,
-            Self::Multiply | Self::Divide | Self::Modulo => 2,
+            Self::Multiply | Self::ImplicitMultiply | Self::Divide | Self::Modulo => 2,
             Self::Add | Self::Subtract => 1,
         }
     }
@@ -293,13 +667,10 @@ impl BiOpr {
         match self {
             Self::Add => l + r,
             Self::Subtract => l - r,
-            Self::Multiply => l * r,
+            Self::Multiply | Self::ImplicitMultiply => l * r,
             Self::Divide => l / r,
             Self::Modulo => l % r,
-            #[cfg(feature = "std")]
-            Self::Power => l.powf(r),
-            #[cfg(not(feature = "std"))]
-            Self::Power => unimplemented!(),
+            Self::Power => float!(f32, powf)(l, r),
         }
     }
 
@@ -308,7 +679,7 @@ impl BiOpr {
         match self {
             Self::Add => Ok(l + r),
             Self::Subtract => Ok(l - r),
-            Self::Multiply => Ok(l * r),
+            Self::Multiply | Self::ImplicitMultiply => Ok(l * r),
             Self::Divide => {
                 if !r.is_zero() {
                     Ok(l / r)
@@ -316,8 +687,8 @@ impl BiOpr {
                     Err("division by zero")
                 }
             },
-            Self::Modulo => Ok(l % r),
-            Self::Power => Ok(l.pow(r)),
+            Self::Modulo => l.checked_rem(r),
+            Self::Power => l.checked_pow(r),
         }
     }
 }
@@ -348,36 +719,70 @@ impl UnOpr {
 
 #[cfg(not(feature = "any_num"))]
 impl Node<f32> {
-    pub fn evaluate(&self) -> f32 {
+    pub fn evaluate(&self) -> Result<f32, Error> { self.evaluate_with(&|v, _| Ok(*v)) }
+}
+
+#[cfg(not(feature = "any_num"))]
+impl<N> Node<N> {
+    fn evaluate_with(&self, number: &impl Fn(&N, &Span) -> Result<f32, Error>) -> Result<f32, Error> {
         match &self.kind {
-            NodeKind::BiOp(l, op, r) => op.operate(l.evaluate(), r.evaluate()),
-            NodeKind::UnOp(op, v) => op.operate(v.evaluate()),
-            NodeKind::Number(v) => *v,
+            NodeKind::BiOp(l, op, r) => Ok(op.operate(l.evaluate_with(number)?, r.evaluate_with(number)?)),
+            NodeKind::UnOp(op, v) => Ok(op.operate(v.evaluate_with(number)?)),
+            NodeKind::Number(v) => number(v, &self.span),
+            NodeKind::Constant(c) => arena::constant(c, &self.span),
+            NodeKind::Group(_, v) => v.evaluate_with(number),
+            NodeKind::Function(c) => {
+                let mut av = Vec::with_capacity(c.args.len());
+                for i in c.args.iter() {
+                    av.push(i.evaluate_with(number)?);
+                }
+
+                f32::execute(&c.name, &av).map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
+                    location: self.span.clone(),
+                })
+            },
         }
     }
 }
 
 #[cfg(feature = "any_num")]
 impl<F: ComputableNumeral> Node<F> {
-    pub fn evaluate(&self) -> Result<F, Error> {
+    pub fn evaluate(&self) -> Result<F, Error> { self.evaluate_with(&|v, _| Ok(v.clone())) }
+}
+
+#[cfg(feature = "any_num")]
+impl<N> Node<N> {
+    fn evaluate_with<F: ComputableNumeral>(
+        &self,
+        number: &impl Fn(&N, &Span) -> Result<F, Error>,
+    ) -> Result<F, Error> {
         match &self.kind {
-            NodeKind::BiOp(l, op, r) => op.operate(l.evaluate()?, r.evaluate()?).map_err(|message| Error {
-                message,
-                location: self.span.clone(),
-            }),
-            NodeKind::UnOp(op, v) => op.operate(v.evaluate()?).map_err(|message| Error {
-                message,
+            NodeKind::BiOp(l, op, r) => op
+                .operate(l.evaluate_with(number)?, r.evaluate_with(number)?)
+                .map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
+                    location: self.span.clone(),
+                }),
+            NodeKind::UnOp(op, v) => op.operate(v.evaluate_with(number)?).map_err(|message| Error {
+                kind: ErrorKind::Evaluation,
+                message: message.into(),
                 location: self.span.clone(),
             }),
-            NodeKind::Number(v) => Ok(v.clone()),
-            NodeKind::Function(f, a) => {
-                let mut av = Vec::with_capacity(a.len());
-                for i in a.iter() {
-                    av.push(i.evaluate()?);
+            NodeKind::Number(v) => number(v, &self.span),
+            NodeKind::Constant(c) => arena::constant(c, &self.span),
+            NodeKind::Group(_, v) => v.evaluate_with(number),
+            NodeKind::Function(c) => {
+                let mut av = Vec::with_capacity(c.args.len());
+                for i in c.args.iter() {
+                    av.push(i.evaluate_with(number)?);
                 }
 
-                F::execute(f, &av).map_err(|message| Error {
-                    message,
+                F::execute(&c.name, &av).map_err(|message| Error {
+                    kind: ErrorKind::Evaluation,
+                    message: message.into(),
                     location: self.span.clone(),
                 })
             },
@@ -386,40 +791,75 @@ impl<F: ComputableNumeral> Node<F> {
 }
 
 #[derive(Debug, Clone)]
-enum Token<Number> {
+enum Token<'src> {
     Operator(OperatorRaw),
-    Number(Number),
+    Number(&'src str),
+    Constant(&'src str),
     BStart(BKind),
     BEnd(BKind),
     Comma,
-    FStart(String, BKind),
+    FStart(&'src str, BKind),
+    /// `\frac` in LaTeX
+    Frac,
+    /// `\sqrt` in LaTeX
+    Sqrt,
+    /// `root` in ASCIIMath
+    Root,
+    /// A LaTeX or ASCIIMath function such as `\sin`, its arguments may or may not be in brackets
+    Command(&'src str),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum BKind {
+/// The kind of a bracket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BKind {
     Round,
     Square,
     Curly,
 }
 
-struct Lexer<'src, Number> {
+struct Lexer<'src> {
+    src: &'src str,
+    dialect: Dialect,
     source: core::str::Chars<'src>,
     start_index: usize,
     current_idx: usize,
     skipped: Option<char>,
-
-    _num: core::marker::PhantomData<Number>,
+    /// Lex unknown names as constants, see [`Arena::parse_with_variables`]
+    variables: bool,
 }
 
-impl<Number: Numeral> Iterator for Lexer<'_, Number> {
-    type Item = Result<Token<Number>, Error>;
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<(Token<'src>, Span), Error>;
 
-    fn next(&mut self) -> Option<Result<Token<Number>, Error>> {
+    fn next(&mut self) -> Option<Result<(Token<'src>, Span), Error>> {
+        let t = self.next_token()?;
+        Some(t.map(|t| (t, self.report_span())))
+    }
+}
+
+impl<'src> Lexer<'src> {
+    fn next_token(&mut self) -> Option<Result<Token<'src>, Error>> {
         self.start_index = self.current_idx;
         let c = self.next_char()?;
 
         match c {
             '+' => Some(Ok(Token::Operator(OperatorRaw::Plus))),
+            // `**`, `-:` and `//` in ASCIIMath
+            '*' | '-' | '/'
+                if self.dialect == Dialect::AsciiMath
+                    && self.peek_char() == Some(match c {
+                        '*' => '*',
+                        '-' => ':',
+                        _ => '/',
+                    }) =>
+            {
+                self.next_char();
+                Some(Ok(Token::Operator(match c {
+                    '*' => OperatorRaw::Multiply,
+                    _ => OperatorRaw::Divide,
+                })))
+            },
             '-' => Some(Ok(Token::Operator(OperatorRaw::Minus))),
             '*' | '×' => Some(Ok(Token::Operator(OperatorRaw::Multiply))),
             '/' | '÷' | '⁄' | '∕' => Some(Ok(Token::Operator(OperatorRaw::Divide))),
@@ -429,55 +869,49 @@ impl<Number: Numeral> Iterator for Lexer<'_, Number> {
             '(' | '[' | '{' => Some(Ok(Token::BStart(btype(c).unwrap()))),
             ')' | ']' | '}' => Some(Ok(Token::BEnd(btype(c).unwrap()))),
             '0'..='9' | '.' => {
-                let mut acc = c.to_string();
-
                 while let Some(c) = self.peek_char() {
                     if matches!(c, '0'..='9' | '.') {
-                        acc.push(c);
                         self.next_char();
                     } else {
                         break;
                     }
                 }
 
-                Some(
-                    acc.parse()
-                        .map_or_else(|_| Err(Error {
-                            message: "number format is incorrect",
-                            location: self.report_span(),
-                        }), |a| Ok(Token::Number(a))),
-                )
+                Some(Ok(Token::Number(self.token_str())))
             },
-            _ if c.is_whitespace() => self.next(),
+            _ if c.is_whitespace() => self.next_token(),
+            '\\' if self.dialect == Dialect::Latex => self.latex_command(),
+            _ if self.dialect == Dialect::AsciiMath && c.is_ascii_alphabetic() => self.ascii_math_word(),
+            _ if self.variables && c.is_alphabetic() => self.variable(),
             _ => {
-                let mut s = c.to_string();
-
-                if let Some(c) = Number::from_constant(repl_greeks(&s)) {
-                    return Some(Ok(Token::Number(c)));
+                let s = repl_greeks(self.token_str());
+                if is_constant(s) {
+                    return Some(Ok(Token::Constant(s)));
                 }
 
-                while let Some(c) = self.next_char() {
-                    if c.is_whitespace() {
+                while let Some(c) = self.peek_char() {
+                    if c.is_whitespace() || matches!(c, ',' | ')' | ']' | '}') {
                         break;
                     }
 
+                    self.next_char();
+
                     if matches!(c, '(' | '[' | '{') {
-                        return Some(Ok(Token::FStart(
-                            repl_greeks(&s).to_string(),
-                            btype(c).unwrap(),
-                        )));
-                    }
+                        let s = self.token_str();
+                        let s = &s[..s.len() - c.len_utf8()];
 
-                    s.push(c);
+                        return Some(Ok(Token::FStart(repl_greeks(s), btype(c).unwrap())));
+                    }
 
-                    let s = repl_greeks(&s);
-                    if let Some(c) = Number::from_constant(s) {
-                        return Some(Ok(Token::Number(c)));
+                    let s = repl_greeks(self.token_str());
+                    if is_constant(s) {
+                        return Some(Ok(Token::Constant(s)));
                     }
                 }
 
                 Some(Err(Error {
-                    message: "this constant is not supported",
+                    kind: ErrorKind::Syntax,
+                    message: "this constant is not supported".into(),
                     location: self.report_span(),
                 }))
             },
@@ -485,6 +919,24 @@ impl<Number: Numeral> Iterator for Lexer<'_, Number> {
     }
 }
 
+impl BKind {
+    pub fn open(self) -> char {
+        match self {
+            Self::Round => '(',
+            Self::Square => '[',
+            Self::Curly => '{',
+        }
+    }
+
+    pub fn close(self) -> char {
+        match self {
+            Self::Round => ')',
+            Self::Square => ']',
+            Self::Curly => '}',
+        }
+    }
+}
+
 fn btype(c: char) -> Option<BKind> {
     match c {
         '(' | ')' => Some(BKind::Round),
@@ -561,7 +1013,153 @@ fn repl_greeks(s: &str) -> &str {
     }
 }
 
-impl<Number> Lexer<'_, Number> {
+impl<'src> Lexer<'src> {
+    fn new(src: &'src str, dialect: Dialect) -> Self {
+        Self {
+            src,
+            dialect,
+            source: src.chars(),
+            start_index: 0,
+            current_idx: 0,
+            skipped: None,
+            variables: false,
+        }
+    }
+
+    fn latex_command(&mut self) -> Option<Result<Token<'src>, Error>> {
+        let name_start = self.current_idx;
+
+        if self.next_char()?.is_ascii_alphabetic() {
+            while self.peek_char().is_some_and(|c| c.is_ascii_alphabetic()) {
+                self.next_char();
+            }
+        }
+
+        let name = &self.src[name_start..self.current_idx];
+        let t = match name {
+            "cdot" | "times" => Token::Operator(OperatorRaw::Multiply),
+            "div" => Token::Operator(OperatorRaw::Divide),
+            "bmod" | "mod" => Token::Operator(OperatorRaw::PercentageSign),
+            "{" => Token::BStart(BKind::Curly),
+            "}" => Token::BEnd(BKind::Curly),
+            "left" | "right" => {
+                // `\left.` has no bracket
+                if self.peek_char() == Some('.') {
+                    self.next_char();
+                }
+
+                return self.next_token();
+            },
+            "," | ";" | ":" | "!" | " " => return self.next_token(),
+            "frac" | "dfrac" | "tfrac" => Token::Frac,
+            "sqrt" => Token::Sqrt,
+            "infty" => Token::Constant("∞"),
+            "operatorname" => {
+                if self.next_char() != Some('{') {
+                    return Some(Err(Error {
+                        kind: ErrorKind::Syntax,
+                        message: "expected `{`".into(),
+                        location: self.report_span(),
+                    }));
+                }
+
+                let name_start = self.current_idx;
+                while self.peek_char().is_some_and(|c| c != '}') {
+                    self.next_char();
+                }
+
+                let name = &self.src[name_start..self.current_idx];
+                self.next_char();
+                Token::Command(name)
+            },
+            _ if latex::FUNCTIONS.contains(&name) => Token::Command(name),
+            _ => {
+                let s = repl_greeks(self.token_str());
+
+                if !is_constant(s) {
+                    return Some(Err(Error {
+                        kind: ErrorKind::Syntax,
+                        message: "this command is not supported".into(),
+                        location: self.report_span(),
+                    }));
+                }
+
+                Token::Constant(s)
+            },
+        };
+
+        Some(Ok(t))
+    }
+
+    /// A name that is not known, a run of letters in the plain dialect and a single letter in the
+    /// others, e.g. `xy` is `x y` in LaTeX and ASCIIMath
+    fn variable(&mut self) -> Option<Result<Token<'src>, Error>> {
+        if self.dialect != Dialect::Plain {
+            return Some(Ok(Token::Constant(self.token_str())));
+        }
+
+        while self.peek_char().is_some_and(char::is_alphabetic) {
+            self.next_char();
+        }
+
+        let s = repl_greeks(self.token_str());
+        if let Some(b) = self.peek_char().filter(|c| matches!(c, '(' | '[' | '{')).and_then(btype) {
+            self.next_char();
+            return Some(Ok(Token::FStart(s, b)));
+        }
+
+        Some(Ok(Token::Constant(s)))
+    }
+
+    fn ascii_math_word(&mut self) -> Option<Result<Token<'src>, Error>> {
+        while self.peek_char().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.next_char();
+        }
+
+        // letters are not separated, e.g. `2pix` is `2 pi x`
+        let Some(word) = asciimath::word(self.token_str()) else {
+            if self.variables {
+                self.rewind(self.start_index + 1);
+                return self.variable();
+            }
+
+            return Some(Err(Error {
+                kind: ErrorKind::Syntax,
+                message: "this constant is not supported".into(),
+                location: self.report_span(),
+            }));
+        };
+        self.rewind(self.start_index + word.len());
+
+        let t = match word {
+            "xx" => Token::Operator(OperatorRaw::Multiply),
+            "mod" => Token::Operator(OperatorRaw::PercentageSign),
+            "frac" => Token::Frac,
+            "root" => Token::Root,
+            _ if asciimath::FUNCTIONS.contains(&word) => Token::Command(word),
+            _ => match asciimath::constant(word) {
+                Some(c) => Token::Constant(c),
+                None => {
+                    return Some(Err(Error {
+                        kind: ErrorKind::Syntax,
+                        message: "this construct is not supported".into(),
+                        location: self.report_span(),
+                    }));
+                },
+            },
+        };
+
+        Some(Ok(t))
+    }
+
+    fn rewind(&mut self, idx: usize) {
+        self.source = self.src[idx..].chars();
+        self.current_idx = idx;
+        self.skipped = None;
+    }
+
+    fn token_str(&self) -> &'src str { &self.src[self.start_index..self.current_idx] }
+
     fn next_char(&mut self) -> Option<char> {
         if self.skipped.is_some() {
             let c = core::mem::take(&mut self.skipped);
@@ -569,9 +1167,8 @@ impl<Number> Lexer<'_, Number> {
             return c;
         }
 
-        self.source.next().map(|a| {
+        self.source.next().inspect(|a| {
             self.current_idx += a.len_utf8();
-            a
         })
     }
 
@@ -588,70 +1185,425 @@ impl<Number> Lexer<'_, Number> {
     fn report_span(&self) -> Span { self.start_index..self.current_idx.max(self.start_index + 1) }
 }
 
-struct Peeking<Inner, Item> {
-    inner: Inner,
-    peeked: Option<Item>,
+/// A stream of tokens with one token of lookahead
+struct Tokens<'src> {
+    lexer: Lexer<'src>,
+    peeked: Option<Option<Result<(Token<'src>, Span), Error>>>,
+    span: Span,
+    /// How many levels the parser is nested in
+    depth: usize,
 }
 
-impl<Inner: Iterator<Item = Item>, Item: Clone> Peeking<Inner, Item> {
-    fn peek(&mut self) -> Option<Item> {
-        if self.peeked.is_none() {
-            let next = self.next();
-            self.peeked = next.clone();
-            next
-        } else {
-            self.peeked.clone()
-        }
-    }
-
-    fn from_iter(inner: Inner) -> Self {
+impl<'src> Tokens<'src> {
+    fn new(src: &'src str, dialect: Dialect) -> Self {
         Self {
-            inner,
+            lexer: Lexer::new(src, dialect),
             peeked: None,
+            span: 0..0,
+            depth: 0,
         }
     }
-}
 
-impl<Inner: Iterator<Item = Item>, Item> Iterator for Peeking<Inner, Item> {
-    type Item = Item;
+    /// Enters a nesting level, failing before the parser recurses deeper than [`arena::MAX_DEPTH`]
+    fn enter(&mut self) -> Result<(), Error> {
+        self.depth += 1;
 
-    fn next(&mut self) -> Option<Item> {
-        if self.peeked.is_some() {
-            core::mem::take(&mut self.peeked)
+        if self.depth > arena::MAX_DEPTH {
+            Err(Error::too_deep(self.report_span()))
         } else {
-            self.inner.next()
+            Ok(())
+        }
+    }
+
+    fn leave(&mut self) { self.depth -= 1; }
+
+    fn peek(&mut self) -> Option<Result<&Token<'src>, &Error>> {
+        let lexer = &mut self.lexer;
+        let t = self.peeked.get_or_insert_with(|| lexer.next()).as_ref()?;
+        Some(t.as_ref().map(|(t, _)| t))
+    }
+
+    fn next(&mut self) -> Option<Result<Token<'src>, Error>> {
+        let t = match self.peeked.take() {
+            Some(t) => t,
+            None => self.lexer.next(),
+        };
+
+        match t {
+            Some(Ok((t, span))) => {
+                self.span = span;
+                Some(Ok(t))
+            },
+            Some(Err(e)) => {
+                self.span = e.location.clone();
+                Some(Err(e))
+            },
+            None => {
+                self.span = self.lexer.report_span();
+                None
+            },
         }
     }
-}
 
-impl<Inner: Iterator<Item = Item>, Item> core::ops::Deref for Peeking<Inner, Item> {
-    type Target = Inner;
+    /// The span of the last token taken from the stream
+    fn report_span(&self) -> Span { self.span.clone() }
 
-    fn deref(&self) -> &Inner { &self.inner }
+    /// Continues after `idx`, dropping the token that was peeked
+    fn rewind(&mut self, idx: usize) {
+        self.peeked = None;
+        self.lexer.rewind(idx);
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+    use std::println;
+
     #[test]
     fn tests() {
-        let lex = crate::Lexer::<f64> {
-            source: "(3(0.1+0.2)-0.9".chars(),
-            start_index: 0,
-            current_idx: 0,
-            skipped: None,
+        let mut lex = crate::Tokens::new("(3(0.1+0.2)-0.9", crate::Dialect::Plain);
+
+        while let Some(t) = lex.next() {
+            println!("{t:?} {:?}", lex.report_span());
+        }
+
+        println!("{:?}", lex.report_span());
+        assert!(lex.peek().is_none());
+
+        let mut lex = crate::Tokens::new("max(12, 3.5π)", crate::Dialect::Plain);
+        let mut spans = alloc::vec::Vec::new();
+        while lex.peek().is_some() {
+            lex.next();
+            spans.push(lex.report_span());
+        }
+        assert_eq!(spans, [0..4, 4..6, 6..7, 8..11, 11..13, 13..14]);
+    }
+
+    #[test]
+    fn tokenize() {
+        use crate::TokenKind::*;
+
+        let tokens: alloc::vec::Vec<_> = crate::tokenize("sin(2π) + @ x,]").collect();
+        assert_eq!(tokens, [
+            (Function, 0..3),
+            (OpenBracket, 3..4),
+            (Number, 4..5),
+            (Constant, 5..7),
+            (CloseBracket, 7..8),
+            (Operator, 9..10),
+            (Invalid, 11..12),
+            (Invalid, 13..14),
+            (Comma, 14..15),
+            (CloseBracket, 15..16),
+        ]);
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn untyped_expr() {
+        let expr = crate::Expr::parse("0.1 + 0.2 * π").unwrap();
+        assert_eq!(expr.evaluate::<f64>().unwrap(), 0.1 + 0.2 * core::f64::consts::PI);
+        assert_eq!(expr.evaluate::<f32>().unwrap(), 0.1 + 0.2 * core::f32::consts::PI);
+        assert_eq!(expr.to_typed::<f64>().unwrap().evaluate().unwrap(), expr.evaluate::<f64>().unwrap());
+
+        #[cfg(feature = "num_rational")]
+        {
+            use num_rational::Ratio;
+
+            let expr = crate::Expr::parse("0.1 + 0.2").unwrap();
+            let v: crate::rational::Rational<num_bigint::BigInt> = expr.evaluate().unwrap();
+            assert_eq!(v.0, Ratio::new(3.into(), 10.into()));
+        }
+
+        let expr = crate::Expr::parse("1..2 + 3").unwrap();
+        assert_eq!(expr.evaluate::<f64>().unwrap_err().location, 0..4);
+    }
+
+    #[test]
+    fn constant_nodes() {
+        use crate::NodeKind;
+
+        let n = crate::to_nodes::<f32>("2π").unwrap();
+        let NodeKind::BiOp(_, _, r) = &n.kind else { panic!("expected a multiplication") };
+        assert!(matches!(&r.kind, NodeKind::Constant(c) if c == "π"));
+
+        let latex = alloc::format!("{}", crate::latex::LatexDisplay { node: &n, src: "2π" });
+        assert_eq!(latex, r"2\pi");
+
+        // whether a constant is supported is only known when evaluating
+        let n = crate::to_nodes::<f32>("1 + i").unwrap();
+        assert_eq!(n.evaluate().unwrap_err().location, 4..5);
+    }
+
+    /// Writes the expression back out from the tree alone, except for function names
+    fn source(n: &crate::Node<crate::Literal>, src: &str) -> alloc::string::String {
+        use crate::{BiOpr, NodeKind, UnOpr};
+        use alloc::format;
+
+        match &n.kind {
+            NodeKind::Number(l) => l.0.clone(),
+            NodeKind::Constant(c) => c.clone(),
+            NodeKind::BiOp(l, op, r) => {
+                let (l, r) = (source(l, src), source(r, src));
+                let op = match op {
+                    BiOpr::Add => "+",
+                    BiOpr::Subtract => "-",
+                    BiOpr::Multiply => "*",
+                    BiOpr::Divide => "/",
+                    BiOpr::Modulo => "%",
+                    BiOpr::Power => "^",
+                    BiOpr::ImplicitMultiply if l.ends_with(|c: char| c.is_ascii_digit()) && r.starts_with(|c: char| c.is_ascii_digit()) => " ",
+                    BiOpr::ImplicitMultiply => "",
+                };
+
+                format!("{l}{op}{r}")
+            },
+            NodeKind::UnOp(op, v) => format!("{}{}", if *op == UnOpr::Plus { "+" } else { "-" }, source(v, src)),
+            NodeKind::Group(k, v) => format!("{}{}{}", k.open(), source(v, src), k.close()),
+            NodeKind::Function(c) => {
+                let args: alloc::vec::Vec<_> = c.args.iter().map(|a| source(a, src)).collect();
+                let args = format!("{}{}{}", c.bracket.open(), args.join(","), c.bracket.close());
+                assert_eq!(args, src[c.args_span.clone()]);
+
+                format!("{}{args}", &src[c.name_span.clone()])
+            },
+        }
+    }
 
-            _num: core::marker::PhantomData::default(),
+    #[test]
+    fn structural_eq() {
+        use crate::{Node, NodeKind};
+
+        let a = crate::to_nodes::<f64>("1 + max(2, π)").unwrap();
+        let b = crate::to_nodes::<f64>("1+max(2,π)").unwrap();
+        assert!(a == b && !a.eq_spanned(&b) && a.eq_spanned(&a.clone()));
+        assert_ne!(a, crate::to_nodes::<f64>("1 + max[2, π]").unwrap());
+        assert_ne!(crate::to_nodes::<f64>("(1)").unwrap(), crate::to_nodes::<f64>("1").unwrap());
+        assert_eq!(crate::Expr::parse("2 ^ 3").unwrap(), crate::Expr::parse("2^3").unwrap());
+
+        let number = |n: f64| Node {
+            kind: NodeKind::Number(n),
+            span: 0..1,
         };
-        let mut lex = crate::Peeking::from_iter(lex);
+        assert_eq!(number(f64::NAN), number(f64::NAN));
+        assert_ne!(number(0.0), number(-0.0));
+
+        #[cfg(feature = "std")]
+        {
+            let set: std::collections::HashSet<_> =
+                ["1+2", "1 + 2", "(1+2)", "2+1"].iter().map(|s| crate::to_nodes::<f64>(s).unwrap()).collect();
+            assert_eq!(set.len(), 3);
+        }
+    }
 
-        while let Some(t) = lex.next() {
-            // println!("{t:?} {:?} {:?} {:?}", lex.inner.report_span(), lex.peek(),
-            // lex.inner.report_span());
-            println!("{t:?} {:?}", lex.inner.report_span());
+    #[test]
+    fn lossless_round_trip() {
+        for s in [
+            "2π[1+2]+max{1,2}(3)",
+            "-(2)^-3%4",
+            "((1))",
+            "2 3(4)[5]",
+            "sqrt{4}π^2/½",
+            "1-(2-3)-4",
+            "max[1,(2),-3]",
+            "2^3^2",
+        ] {
+            let expr = crate::Expr::parse(s).unwrap();
+            assert_eq!(source(&expr, s), s);
         }
+    }
 
-        println!("{:?}", lex.inner.report_span());
-        lex.peek();
-        println!("{:?}", lex.inner.report_span());
+    #[cfg(not(feature = "any_num"))]
+    #[test]
+    fn evaluate_f32() {
+        let eval = |s| crate::to_nodes::<f32>(s).unwrap().evaluate().unwrap();
+
+        assert_eq!(eval("2^10"), 1024.0);
+        assert_eq!(eval("floor(2.5) + ceil(2.5) + round(2.5) + trunc(2.5)"), 10.0);
+        assert!((eval("sqrt(16) + cbrt(27) + ln(e) + log(1000) + log(8, 2)") - 14.0).abs() < 1e-5);
+        assert!((eval("sin(π/2) + cos(0) + tan(0) + arctan(1)*4/π") - 3.0).abs() < 1e-5);
+        assert!((eval("cosh(0) + sinh(0) + tanh(0) + arcsinh(0) + arccosh(1)") - 1.0).abs() < 1e-5);
+        assert!(crate::to_nodes::<f32>("ln(-1)").unwrap().evaluate().is_err());
+
+        // without `std` the functions come from `libm`, even though the test harness links `std`
+        #[cfg(not(feature = "std"))]
+        for (s, expected) in [
+            ("sqrt(2)", libm::sqrtf(2.0)),
+            ("sin(1)", libm::sinf(1.0)),
+            ("2^0.3", libm::powf(2.0, 0.3)),
+        ] {
+            assert_eq!(eval(s).to_bits(), expected.to_bits(), "{s}");
+        }
+    }
+
+    #[cfg(all(feature = "any_num", not(feature = "std")))]
+    #[test]
+    fn evaluate_libm() {
+        for (s, expected) in [
+            ("sqrt(2)", libm::sqrt(2.0)),
+            ("sin(1)", libm::sin(1.0)),
+            ("2^0.3", libm::pow(2.0, 0.3)),
+            ("abs(-1.5)", 1.5),
+        ] {
+            let v: f64 = crate::to_nodes(s).unwrap().evaluate().unwrap();
+            assert_eq!(v.to_bits(), expected.to_bits(), "{s}");
+        }
+    }
+
+    /// Inputs that used to abort the evaluation
+    #[cfg(feature = "any_num")]
+    const CORPUS: &[&str] = &[
+        "(-8)^(1/2)",
+        "(-8)^(1/3)",
+        "5%0",
+        "0%0",
+        "0^(-1)",
+        "0^(1/2)",
+        "ln(0)",
+        "ln(-1)",
+        "2^(10^30)",
+        "9^9^9",
+        "10^10^10^10",
+        "(1/3)^(10^9)",
+        "4^(99999999999/2)",
+        "e^(-1.5)",
+        "e^(10^9)",
+        "e^(-10^9)",
+        "sqrt(-4)",
+        "tan(π/2)",
+        "ln(10^100)",
+        "ln(10^10000)",
+        "min()",
+        "max()",
+        "1/0",
+        "1.2.3",
+        ".",
+        "((",
+        "sin(",
+        "\\pi",
+    ];
+
+    /// Fragments that random inputs are assembled from
+    #[cfg(feature = "any_num")]
+    const FRAGMENTS: &[&str] = &[
+        "0", "1", "2", "9", "10", "2.5", ".", "(", ")", "[", "]", "{", "}", "+", "-", "*", "/", "%",
+        "^", ",", " ", "π", "e", "i", "½", "sqrt(", "cbrt(", "ln(", "log(", "sin(", "tan(",
+        "arctan2(", "min(", "max(", "conj(", "abc", "é", "\\pi", "1.2.3", "\\frac{", "\\sqrt[", "\\left(",
+        "\\right)", "\\cdot", "\\sin", "\\operatorname{", "root", "frac", "xx", "-:", "oo", "sqrt ", "sum_",
+    ];
+
+    #[cfg(feature = "any_num")]
+    fn assert_no_panic<T: crate::traits::ComputableNumeral>(s: &str) {
+        if let Ok(n) = crate::to_nodes::<T>(s) {
+            let _ = n.evaluate();
+        }
+    }
+
+    #[cfg(feature = "any_num")]
+    fn no_panic_inputs() -> impl Iterator<Item = alloc::string::String> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        let random = (0..300).map(move |_| {
+            let len = next() % 12 + 1;
+            (0..len).map(|_| FRAGMENTS[next() % FRAGMENTS.len()]).collect()
+        });
+
+        CORPUS.iter().map(|s| (*s).into()).chain(random)
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn never_panics() {
+        for s in no_panic_inputs() {
+            let mut end = 0;
+            for (_, span) in crate::tokenize(&s) {
+                assert!(end <= span.start && span.start < span.end && s.get(span.clone()).is_some());
+                end = span.end;
+            }
+
+            if let Ok(n) = crate::Expr::parse(&s) {
+                let _ = alloc::format!("{}", crate::pretty::PrettyDisplay { node: &n, src: &s });
+            }
+
+            for dialect in [crate::Dialect::Latex, crate::Dialect::AsciiMath] {
+                if let Ok(n) = crate::to_nodes_with::<f64>(&s, dialect) {
+                    let _ = n.evaluate();
+                }
+            }
+
+            assert_no_panic::<f32>(&s);
+            assert_no_panic::<f64>(&s);
+
+            #[cfg(feature = "num_complex")]
+            {
+                assert_no_panic::<num_complex::Complex<f32>>(&s);
+                assert_no_panic::<num_complex::Complex<f64>>(&s);
+            }
+
+            #[cfg(feature = "num_rational")]
+            assert_no_panic::<crate::rational::Rational<num_bigint::BigInt>>(&s);
+
+            #[cfg(all(feature = "num_rational", feature = "num_complex"))]
+            assert_no_panic::<crate::rational::complex::ComplexRational<num_bigint::BigInt>>(&s);
+        }
+    }
+
+    #[test]
+    fn deep_input() {
+        use crate::arena::MAX_DEPTH;
+        use alloc::{format, string::String};
+
+        type Chain = fn(usize) -> String;
+
+        for (chain, long) in [
+            (|n| "2^".repeat(n) + "2", 100_000),
+            (|n| "(".repeat(n) + "1" + &")".repeat(n), 100_000),
+            (|n| "-".repeat(n) + "1", 100_000),
+        ] as [(Chain, usize); 3]
+        {
+            let s = chain(MAX_DEPTH - 1);
+            let n = crate::to_nodes::<f64>(&s).unwrap();
+            #[cfg(feature = "any_num")]
+            n.evaluate().unwrap();
+            let _ = format!("{n} {}", crate::latex::LatexDisplay { node: &n, src: &s });
+            let _ = format!("{}", crate::mathml::MathMlDisplay { node: &n, src: &s });
+            let _ = format!("{}", crate::pretty::PrettyDisplay { node: &n, src: &s });
+            let _ = format!("{}", crate::speech::SpokenDisplay { node: &n, src: &s });
+
+            let (arena, root) = crate::parse(&s).unwrap();
+            let v: Result<f32, _> = arena.evaluate(root);
+            v.unwrap();
+
+            for s in [chain(MAX_DEPTH), chain(long)] {
+                let e = crate::parse(&s).err().unwrap();
+                assert_eq!(e.kind, crate::ErrorKind::TooDeep);
+                assert!(e.location.end <= s.len() && e.location.start < e.location.end);
+            }
+        }
+
+        // a chain of left-associative operations is long, not deep
+        for (chain, len) in [
+            (|n| "1+".repeat(n) + "1", 1000),
+            (|n| "2*".repeat(n) + "1", 1000),
+            (|n| "1-2+".repeat(n) + "1", 500),
+        ] as [(Chain, usize); 3]
+        {
+            let s = chain(len);
+            let n = crate::to_nodes::<f64>(&s).unwrap();
+            #[cfg(feature = "any_num")]
+            n.evaluate().unwrap();
+            let _ = format!("{n} {}", crate::latex::LatexDisplay { node: &n, src: &s });
+
+            let (arena, root) = crate::parse(&s).unwrap();
+            let v: Result<f32, _> = arena.evaluate(root);
+            v.unwrap();
+        }
     }
 }
@@ -0,0 +1,289 @@
+use core::fmt::{self, Write};
+use alloc::string::String;
+use crate::*;
+use crate::latex::{needs_brackets, number, number_parts, Side};
+
+/// MathML Core presentation markup with only the brackets that are needed to read it correctly
+pub struct MathMlDisplay<'a, T> {
+    pub node: &'a Node<T>,
+    /// The same as [`LatexDisplay::src`](crate::latex::LatexDisplay::src)
+    pub src: &'a str,
+}
+
+impl<T: fmt::Display + NumberForm> fmt::Display for MathMlDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::new();
+        self.write(self.node, &mut s);
+        write!(f, "<math>{s}</math>")
+    }
+}
+
+impl<T: fmt::Display + NumberForm> MathMlDisplay<'_, T> {
+    /// Writes `node` as exactly one element
+    fn write(&self, node: &Node<T>, s: &mut String) {
+        match &node.kind {
+            NodeKind::Number(n) => number_text(&number(n, &node.span, self.src), s),
+            NodeKind::Constant(c) => match c.as_str() {
+                "c_m/s" => element("mi", "c", s),
+                c if c == "∞" || c.chars().all(char::is_alphabetic) => element("mi", c, s),
+                // vulgar fractions
+                c => element("mn", c, s),
+            },
+            NodeKind::Group(_, v) => self.write(v, s),
+            NodeKind::UnOp(op, v) => {
+                s.push_str("<mrow>");
+                element("mo", match op {
+                    UnOpr::Plus => "+",
+                    UnOpr::Minus => "−",
+                }, s);
+                self.operand(v, needs_brackets(node, Side::Right), s);
+                s.push_str("</mrow>");
+            },
+            NodeKind::BiOp(l, BiOpr::Divide, r) => {
+                s.push_str("<mfrac>");
+                self.write(l, s);
+                self.write(r, s);
+                s.push_str("</mfrac>");
+            },
+            NodeKind::BiOp(l, BiOpr::Power, r) => {
+                s.push_str("<msup>");
+                self.operand(l, needs_brackets(node, Side::Left), s);
+                self.write(r, s);
+                s.push_str("</msup>");
+            },
+            NodeKind::BiOp(l, op, r) => {
+                s.push_str("<mrow>");
+                self.operand(l, needs_brackets(node, Side::Left), s);
+                element("mo", match op {
+                    BiOpr::Add => "+",
+                    BiOpr::Subtract => "−",
+                    BiOpr::Multiply => "⋅",
+                    // invisible times
+                    BiOpr::ImplicitMultiply => "\u{2062}",
+                    _ => "mod",
+                }, s);
+                self.operand(r, needs_brackets(node, Side::Right), s);
+                s.push_str("</mrow>");
+            },
+            NodeKind::Function(c) => self.function(c, s),
+        }
+    }
+
+    fn function(&self, c: &Call<T>, s: &mut String) {
+        let a = &c.args;
+
+        match (c.name.as_str(), a.len()) {
+            ("sqrt" | "√", 1) => self.wrap("<msqrt>", &a[0], "</msqrt>", s),
+            ("cbrt" | "∛", 1) => self.wrap("<mroot>", &a[0], "<mn>3</mn></mroot>", s),
+            ("abs", 1) => self.fenced("|", &a[0], "|", s),
+            ("floor", 1) => self.fenced("⌊", &a[0], "⌋", s),
+            ("ceil", 1) => self.fenced("⌈", &a[0], "⌉", s),
+            ("conj", 1) => self.wrap("<mover accent=\"true\">", &a[0], "<mo>‾</mo></mover>", s),
+            ("log", 2) => {
+                self.wrap("<mrow><msub><mi>log</mi>", &a[1], "</msub><mo>\u{2061}</mo>", s);
+                self.fenced("(", &a[0], ")", s);
+                s.push_str("</mrow>");
+            },
+            (name, _) => {
+                s.push_str("<mrow>");
+                element("mi", name, s);
+                // function application
+                s.push_str("<mo>\u{2061}</mo><mrow><mo>(</mo>");
+
+                for (i, r) in a.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str("<mo>,</mo>");
+                    }
+
+                    self.write(r, s);
+                }
+
+                s.push_str("<mo>)</mo></mrow></mrow>");
+            },
+        }
+    }
+
+    fn wrap(&self, open: &str, node: &Node<T>, close: &str, s: &mut String) {
+        s.push_str(open);
+        self.write(node, s);
+        s.push_str(close);
+    }
+
+    fn fenced(&self, open: &str, node: &Node<T>, close: &str, s: &mut String) {
+        s.push_str("<mrow>");
+        element("mo", open, s);
+        self.write(node, s);
+        element("mo", close, s);
+        s.push_str("</mrow>");
+    }
+
+    fn operand(&self, node: &Node<T>, brackets: bool, s: &mut String) {
+        if !brackets {
+            return self.write(node, s);
+        }
+
+        let k = match &node.kind {
+            NodeKind::Group(k, _) => *k,
+            _ => BKind::Round,
+        };
+
+        self.fenced(k.open().encode_utf8(&mut [0; 4]), node, k.close().encode_utf8(&mut [0; 4]), s);
+    }
+}
+
+/// Writes an element containing escaped text
+fn element(tag: &str, text: &str, s: &mut String) {
+    let _ = write!(s, "<{tag}>");
+
+    for c in text.chars() {
+        match c {
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            '&' => s.push_str("&amp;"),
+            c => s.push(c),
+        }
+    }
+
+    let _ = write!(s, "</{tag}>");
+}
+
+/// Writes the text of a number as one element, which is more than an `<mn>` for number types that
+/// display fractions, signs or imaginary parts, e.g. `(-1 / 2)` or `1+2i`
+fn number_text(text: &str, s: &mut String) {
+    let t = text.trim();
+    let (sum, fraction) = number_parts(t);
+
+    if let Some(i) = sum {
+        s.push_str("<mrow>");
+        number_text(&t[..i], s);
+        element("mo", if t[i..].starts_with('+') { "+" } else { "−" }, s);
+        number_text(&t[i + 1..], s);
+        s.push_str("</mrow>");
+    } else if let Some(v) = t.strip_prefix('-') {
+        s.push_str("<mrow><mo>−</mo>");
+        number_text(v, s);
+        s.push_str("</mrow>");
+    } else if let Some(i) = fraction {
+        s.push_str("<mfrac>");
+        number_text(&t[..i], s);
+        number_text(&t[i + 1..], s);
+        s.push_str("</mfrac>");
+    } else if let Some(v) = t.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        // a negative part keeps its brackets, e.g. `2 + (-1 / 2)i`
+        if v.starts_with('-') {
+            s.push_str("<mrow><mo>(</mo>");
+            number_text(v, s);
+            s.push_str("<mo>)</mo></mrow>");
+        } else {
+            number_text(v, s);
+        }
+    } else if let Some(v) = t.strip_suffix('i').filter(|v| !v.is_empty()) {
+        s.push_str("<mrow>");
+        number_text(v, s);
+        // invisible times
+        s.push_str("<mo>\u{2062}</mo><mi>i</mi></mrow>");
+    } else if t == "inf" {
+        element("mi", "∞", s);
+    } else if t.chars().all(char::is_alphabetic) {
+        element("mi", t, s);
+    } else {
+        element("mn", t, s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec::Vec};
+
+    fn mathml(s: &str) -> String {
+        let node = crate::Expr::parse(s).unwrap();
+        MathMlDisplay { node: &node, src: s }.to_string()
+    }
+
+    #[test]
+    fn snapshots() {
+        for (s, expected) in [
+            ("1+2", "<mrow><mn>1</mn><mo>+</mo><mn>2</mn></mrow>"),
+            ("1/(2+3)", "<mfrac><mn>1</mn><mrow><mn>2</mn><mo>+</mo><mn>3</mn></mrow></mfrac>"),
+            ("-(2^2)", "<mrow><mo>−</mo><msup><mn>2</mn><mn>2</mn></msup></mrow>"),
+            ("-2^2", "<msup><mrow><mo>(</mo><mrow><mo>−</mo><mn>2</mn></mrow><mo>)</mo></mrow><mn>2</mn></msup>"),
+            ("2π", "<mrow><mn>2</mn><mo>\u{2062}</mo><mi>π</mi></mrow>"),
+            ("sqrt(½)", "<msqrt><mn>½</mn></msqrt>"),
+            ("cbrt(8)", "<mroot><mn>8</mn><mn>3</mn></mroot>"),
+            ("[1-2]*3", "<mrow><mrow><mo>[</mo><mrow><mn>1</mn><mo>−</mo><mn>2</mn></mrow><mo>]</mo></mrow><mo>⋅</mo><mn>3</mn></mrow>"),
+            ("max(1, 2)", "<mrow><mi>max</mi><mo>\u{2061}</mo><mrow><mo>(</mo><mn>1</mn><mo>,</mo><mn>2</mn><mo>)</mo></mrow></mrow>"),
+            ("f<(1)", "<mrow><mi>f&lt;</mi><mo>\u{2061}</mo><mrow><mo>(</mo><mn>1</mn><mo>)</mo></mrow></mrow>"),
+        ] {
+            assert_eq!(mathml(s), alloc::format!("<math>{expected}</math>"), "{s}");
+        }
+    }
+
+    #[test]
+    fn number_types() {
+        for (n, expected) in [
+            ("(-1 / 2)", "<mrow><mo>(</mo><mrow><mo>−</mo><mfrac><mn>1</mn><mn>2</mn></mfrac></mrow><mo>)</mo></mrow>"),
+            ("(1 / 2)", "<mfrac><mn>1</mn><mn>2</mn></mfrac>"),
+            ("1 + (1 / 2)i", "<mrow><mn>1</mn><mo>+</mo><mrow><mfrac><mn>1</mn><mn>2</mn></mfrac><mo>\u{2062}</mo><mi>i</mi></mrow></mrow>"),
+            ("-1-2i", "<mrow><mrow><mo>−</mo><mn>1</mn></mrow><mo>−</mo><mrow><mn>2</mn><mo>\u{2062}</mo><mi>i</mi></mrow></mrow>"),
+            ("i", "<mi>i</mi>"),
+            ("-inf", "<mrow><mo>−</mo><mi>∞</mi></mrow>"),
+            ("1.5", "<mn>1.5</mn>"),
+        ] {
+            let node: Node<Literal> = crate::build::num(Literal(n.into()));
+            assert_eq!(MathMlDisplay { node: &node, src: "" }.to_string(), alloc::format!("<math>{expected}</math>"), "{n}");
+        }
+    }
+
+    #[test]
+    fn built() {
+        use crate::build::*;
+
+        for (n, expected) in [
+            (
+                num::<f64>(-2.0).pow(num(2.0)),
+                "<msup><mrow><mo>(</mo><mrow><mo>−</mo><mn>2</mn></mrow><mo>)</mo></mrow><mn>2</mn></msup>",
+            ),
+            (num(2.0) * num(-3.0), "<mrow><mn>2</mn><mo>⋅</mo><mrow><mo>(</mo><mrow><mo>−</mo><mn>3</mn></mrow><mo>)</mo></mrow></mrow>"),
+        ] {
+            assert_eq!(MathMlDisplay { node: &n, src: "" }.to_string(), alloc::format!("<math>{expected}</math>"));
+        }
+    }
+
+    /// Checks that every element is closed and that scripts and fractions have two children
+    #[test]
+    fn well_formed() {
+        for s in [
+            "1+2*3", "(1/2)^2", "2^3^2", "-2^2", "2*-3", "sin(π/2)", "sqrt(2)cbrt[3]", "5%3", "log(8, 2)",
+            "abs(-1) + floor(½) + ceil(e) + conj(i)", "max(1, 2, 3)", "2(1+π)^(1/2)",
+        ] {
+            let out = mathml(s);
+            // (tag, children)
+            let mut stack: Vec<(&str, usize)> = Vec::new();
+            let mut rest = out.as_str();
+
+            while let Some(i) = rest.find('<') {
+                let end = rest[i..].find('>').unwrap() + i;
+                let tag = rest[i + 1..end].split(' ').next().unwrap();
+                rest = &rest[end + 1..];
+
+                if let Some(tag) = tag.strip_prefix('/') {
+                    let (open, children) = stack.pop().unwrap();
+                    assert_eq!(open, tag, "{s}");
+
+                    if matches!(tag, "mfrac" | "msup" | "msub" | "mroot" | "mover") {
+                        assert_eq!(children, 2, "{s}: {out}");
+                    }
+                } else {
+                    if let Some(parent) = stack.last_mut() {
+                        parent.1 += 1;
+                    }
+
+                    stack.push((tag, 0));
+                }
+            }
+
+            assert!(stack.is_empty(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Substituting variables and evaluating the parts of a tree that are already known.
+//!
+//! Variables are [`NodeKind::Constant`]s that neither a [`Context`] nor [`FromConstant`] knows,
+//! e.g. `x` in `num(2) * var("x")` built with [`build`].
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+};
+#[cfg(feature = "any_num")]
+use alloc::{boxed::Box, vec::Vec};
+use crate::*;
+use crate::visit::{Fold, Visitor};
+
+/// The values of the variables that are already known
+#[derive(Debug, Clone)]
+pub struct Context<N> {
+    values: BTreeMap<String, N>,
+}
+
+impl<N> Default for Context<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<N> Context<N> {
+    pub fn new() -> Self { Self { values: BTreeMap::new() } }
+
+    /// Sets the value of `name`, which takes precedence over a constant of the same name
+    pub fn set(&mut self, name: &str, value: N) -> &mut Self {
+        self.values.insert(String::from(name), value);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&N> { self.values.get(name) }
+}
+
+struct Substitute<'a, N> {
+    name: &'a str,
+    value: &'a Node<N>,
+}
+
+impl<N: Clone> Fold<N> for Substitute<'_, N> {
+    fn fold_constant(&mut self, c: String, span: Span) -> Node<N> {
+        if c == self.name {
+            self.value.clone()
+        } else {
+            Node {
+                kind: NodeKind::Constant(c),
+                span,
+            }
+        }
+    }
+}
+
+struct Variables<'a>(BTreeSet<&'a str>);
+
+impl<'a, N> Visitor<'a, N> for Variables<'a> {
+    fn visit_constant(&mut self, c: &'a str, _span: &'a Span) {
+        if !traits::is_constant(c) {
+            self.0.insert(c);
+        }
+    }
+}
+
+impl<N> Node<N> {
+    /// The names of the constants that are not in [`traits::CONSTANTS`], e.g. `x` and `y` in
+    /// `x^2 + π y`
+    pub fn variables(&self) -> BTreeSet<&str> {
+        let mut v = Variables(BTreeSet::new());
+        v.visit_node(self);
+        v.0
+    }
+}
+
+impl<N: Clone> Node<N> {
+    /// Replaces every `name` with `value`, the renderers add the brackets that `value` needs
+    pub fn substitute(&self, name: &str, value: &Node<N>) -> Node<N> {
+        Substitute { name, value }.fold_node(self.clone())
+    }
+}
+
+#[cfg(feature = "any_num")]
+impl<N: ComputableNumeral> Node<N> {
+    /// Evaluates every subtree that does not depend on an unknown variable and leaves the rest in
+    /// place, e.g. `2*3*x + 4 - 1` becomes `6 * x + 3`. Evaluated numbers have empty spans.
+    pub fn partially_evaluate(&self, ctx: &Context<N>) -> Result<Node<N>, Error> {
+        let span = self.span.clone();
+
+        match &self.kind {
+            NodeKind::Number(_) => Ok(self.clone()),
+            NodeKind::Constant(c) => match ctx.get(c).cloned().or_else(|| N::from_constant(c)) {
+                Some(n) => Ok(folded(n, &span)),
+                None => Ok(self.clone()),
+            },
+            NodeKind::Group(k, v) => match v.partially_evaluate(ctx)? {
+                v @ Node { kind: NodeKind::Number(_), .. } => Ok(v),
+                v => Ok(Node {
+                    kind: NodeKind::Group(*k, Box::new(v)),
+                    span,
+                }),
+            },
+            NodeKind::UnOp(op, v) => match v.partially_evaluate(ctx)? {
+                Node { kind: NodeKind::Number(n), .. } => {
+                    let n = op.operate(n).map_err(|message| Error {
+                        kind: ErrorKind::Evaluation,
+                        message: message.into(),
+                        location: span.clone(),
+                    })?;
+                    Ok(folded(n, &span))
+                },
+                v => Ok(Node {
+                    kind: NodeKind::UnOp(*op, Box::new(v)),
+                    span,
+                }),
+            },
+            NodeKind::BiOp(l, op, r) => bi_op(l.partially_evaluate(ctx)?, *op, r.partially_evaluate(ctx)?, span),
+            NodeKind::Function(c) => {
+                let mut args = Vec::with_capacity(c.args.len());
+                for a in c.args.iter() {
+                    args.push(a.partially_evaluate(ctx)?);
+                }
+
+                // a function that `N` does not know stays in place, like a variable
+                if N::knows(&c.name, args.len()) && args.iter().all(|a| matches!(a.kind, NodeKind::Number(_))) {
+                    let av: Vec<N> = args
+                        .into_iter()
+                        .filter_map(|a| match a.kind {
+                            NodeKind::Number(n) => Some(n),
+                            _ => None,
+                        })
+                        .collect();
+
+                    let n = N::execute(&c.name, &av).map_err(|message| Error {
+                        kind: ErrorKind::Evaluation,
+                        message: message.into(),
+                        location: span.clone(),
+                    })?;
+                    return Ok(folded(n, &span));
+                }
+
+                Ok(Node {
+                    kind: NodeKind::Function(Call {
+                        name: c.name.clone(),
+                        name_span: c.name_span.clone(),
+                        bracket: c.bracket,
+                        args_span: c.args_span.clone(),
+                        args,
+                    }),
+                    span,
+                })
+            },
+        }
+    }
+}
+
+/// A number that was evaluated, its span is empty so that it is not taken from the source
+#[cfg(feature = "any_num")]
+fn folded<N>(n: N, span: &Span) -> Node<N> {
+    Node {
+        kind: NodeKind::Number(n),
+        span: span.start..span.start,
+    }
+}
+
+/// Whether `a op (b inner c)` or `(a inner b) op c` can be evaluated in another order
+#[cfg(feature = "any_num")]
+fn chain(inner: BiOpr, op: BiOpr) -> bool {
+    use BiOpr::*;
+
+    matches!(
+        (inner, op),
+        (Add | Subtract, Add | Subtract) | (Multiply | ImplicitMultiply, Multiply | ImplicitMultiply)
+    )
+}
+
+#[cfg(feature = "any_num")]
+fn bi_op<N: ComputableNumeral>(l: Node<N>, op: BiOpr, r: Node<N>, span: Span) -> Result<Node<N>, Error> {
+    let operate = |op: BiOpr, a: N, b: N| {
+        op.operate(a, b).map_err(|message| Error {
+            kind: ErrorKind::Evaluation,
+            message: message.into(),
+            location: span.clone(),
+        })
+    };
+    let is_number = |n: &Node<N>| matches!(n.kind, NodeKind::Number(_));
+    let multiply = matches!(op, BiOpr::Multiply | BiOpr::ImplicitMultiply);
+
+    // `(x ± a) ± b` and `a * (b * x)` are reassociated through the brackets
+    let reassociates = |n: &Node<N>, left: bool| match &n.kind {
+        NodeKind::Group(_, v) => match &v.kind {
+            NodeKind::BiOp(a, inner, b) => chain(*inner, op) && (is_number(a) || left && is_number(b)),
+            _ => false,
+        },
+        _ => false,
+    };
+    let ungroup = |n: Node<N>| match n.kind {
+        NodeKind::Group(_, v) => *v,
+        kind => Node { kind, span: n.span },
+    };
+    let l = if is_number(&r) && reassociates(&l, true) { ungroup(l) } else { l };
+    let r = if multiply && is_number(&l) && reassociates(&r, false) { ungroup(r) } else { r };
+
+    match (l.kind, r.kind) {
+        (NodeKind::Number(a), NodeKind::Number(b)) => Ok(folded(operate(op, a, b)?, &span)),
+        // `x ± a ± b` is `x ± (a ± b)` and `x * a * b` is `x * (a * b)`
+        (NodeKind::BiOp(x, inner, a), NodeKind::Number(b)) if chain(inner, op) && is_number(&a) => {
+            let NodeKind::Number(a) = a.kind else { unreachable!() };
+            let combined = match (inner, op) {
+                (BiOpr::Add, BiOpr::Subtract) | (BiOpr::Subtract, BiOpr::Add) => BiOpr::Subtract,
+                (BiOpr::Add | BiOpr::Subtract, _) => BiOpr::Add,
+                _ => BiOpr::Multiply,
+            };
+
+            Ok(Node {
+                kind: NodeKind::BiOp(x, inner, Box::new(folded(operate(combined, a, b)?, &span))),
+                span,
+            })
+        },
+        // `a ± x ± b` is `(a ± b) ± x` and `a * x * b` is `(a * b) * x`
+        (NodeKind::BiOp(a, inner, x), NodeKind::Number(b)) if chain(inner, op) && is_number(&a) => {
+            let NodeKind::Number(a) = a.kind else { unreachable!() };
+
+            Ok(Node {
+                kind: NodeKind::BiOp(Box::new(folded(operate(op, a, b)?, &span)), inner, x),
+                span,
+            })
+        },
+        // `a * b x` is `(a * b) x`
+        (NodeKind::Number(a), NodeKind::BiOp(b, inner, x)) if chain(inner, op) && multiply && is_number(&b) => {
+            let NodeKind::Number(b) = b.kind else { unreachable!() };
+
+            Ok(Node {
+                kind: NodeKind::BiOp(Box::new(folded(operate(op, a, b)?, &span)), inner, x),
+                span,
+            })
+        },
+        (lk, rk) => Ok(Node {
+            kind: NodeKind::BiOp(Box::new(Node { kind: lk, span: l.span }), op, Box::new(Node { kind: rk, span: r.span })),
+            span,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn substitute() {
+        let n: Node<f64> = num(2) * var("x") + func("sin", [var("x")]) / var("y");
+        let n = n.substitute("x", &(num(1) + var("y")));
+        assert_eq!(n.to_string(), "2 * (1 + y) + sin(1 + y) / y");
+        assert_eq!(n.variables().into_iter().collect::<alloc::vec::Vec<_>>(), ["y"]);
+
+        let n: Node<f64> = num(2) * var("π") + func("f", [var("i")]);
+        assert!(n.variables().is_empty());
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn partially_evaluate() {
+        let x = || var::<f64>("x");
+        let ctx = Context::new();
+
+        for (n, expected) in [
+            (num(2) * num(3) * x() + num(4) - num(1), "6 * x + 3"),
+            (num(1) - x() + num(2) - num(4), "(-1) - x"),
+            (x() - num(4) + num(1), "x - 3"),
+            (num(2) * (num(3) * x()) * num(4), "24 * x"),
+            (to_nodes("(1 + 2)").unwrap() * x().pow(to_nodes("4/2").unwrap()), "3 * x^2"),
+            (func("max", [num(1), num(2)]) + func("max", [x(), num(1) + var("π") - var("π")]), "2 + max(x, 1)"),
+            (-num(2).pow(num(2)) * -x(), "(-4) * -x"),
+        ] {
+            assert_eq!(n.partially_evaluate(&ctx).unwrap().to_string(), expected);
+        }
+
+        let n = num(2) * num(3) * x() + num(4) - num(1);
+        assert_eq!(n.partially_evaluate(Context::new().set("x", 2.0)).unwrap(), num(15));
+
+        let n = to_nodes::<f64>("1/(2 - 2)").unwrap() + x();
+        assert_eq!(n.partially_evaluate(&ctx).unwrap_err().location, 0..9);
+
+        let n = func("f", [num(1) + num(2), x()]) + func("f", [num(1) + num(2)]);
+        assert_eq!(n.partially_evaluate(&ctx).unwrap().to_string(), "f(3, x) + f(3)");
+
+        let n = func("sin", [num(1), num(2)]) + func("max", [num(1), num(2), num(3)]);
+        assert_eq!(n.partially_evaluate(&ctx).unwrap().to_string(), "sin(1, 2) + 3");
+    }
+
+    #[cfg(feature = "any_num")]
+    #[test]
+    fn parsed() {
+        let ctx = Context::new();
+
+        for (s, expected) in [
+            ("2*3*x + 4 - 1", "6 * x + 3"),
+            ("2*(3*π)*4", "24 * y"),
+            ("(x - 4) + 1", "x - 3"),
+            ("2 - (3 - x)", "2 - (3 - x)"),
+        ] {
+            let n = to_nodes_with_variables::<f64>(s).unwrap().substitute("π", &var("y"));
+            assert_eq!(n.partially_evaluate(&ctx).unwrap().to_string(), expected, "{s}");
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use core::fmt::{self, Write};
+use alloc::{string::String, vec, vec::Vec};
+use crate::*;
+use crate::latex::{needs_brackets, number, unwrap, Side};
+
+/// A drawing for terminals with fractions over bars, raised exponents, radical signs and stretched
+/// brackets
+pub struct PrettyDisplay<'a, T> {
+    pub node: &'a Node<T>,
+    /// The same as [`LatexDisplay::src`](crate::latex::LatexDisplay::src)
+    pub src: &'a str,
+}
+
+impl<T: fmt::Display + NumberForm> fmt::Display for PrettyDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, l) in self.block(self.node).lines.iter().enumerate() {
+            if i != 0 {
+                f.write_char('\n')?;
+            }
+
+            f.write_str(l.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: fmt::Display + NumberForm> PrettyDisplay<'_, T> {
+    fn block(&self, node: &Node<T>) -> Block {
+        match &node.kind {
+            NodeKind::Number(n) => Block::text(&number(n, &node.span, self.src)),
+            NodeKind::Constant(c) => Block::text(if c == "c_m/s" { "c" } else { c }),
+            NodeKind::Group(_, v) => self.block(v),
+            NodeKind::UnOp(op, v) => {
+                let v = self.operand(v, needs_brackets(node, Side::Right));
+                Block::text(match op {
+                    UnOpr::Plus => "+",
+                    UnOpr::Minus => "-",
+                })
+                .beside(v)
+            },
+            NodeKind::BiOp(l, BiOpr::Divide, r) => {
+                let (n, d) = (self.block(l), self.block(r));
+                let width = n.width.max(d.width);
+                let baseline = n.height();
+
+                let mut lines = n.centre(width).lines;
+                lines.push("─".repeat(width));
+                lines.extend(d.centre(width).lines);
+
+                Block { lines, width, baseline }
+            },
+            NodeKind::BiOp(l, BiOpr::Power, r) => {
+                let base = self.operand(l, needs_brackets(node, Side::Left));
+                let exp = self.block(r);
+
+                let mut lines: Vec<String> = exp.lines.iter().map(|l| " ".repeat(base.width) + l).collect();
+                lines.extend(base.lines.iter().map(|l| l.clone() + &" ".repeat(exp.width)));
+
+                Block {
+                    lines,
+                    width: base.width + exp.width,
+                    baseline: exp.height() + base.baseline,
+                }
+            },
+            NodeKind::BiOp(l, op, r) => {
+                let fraction = |n: &Node<T>| matches!(unwrap(n).kind, NodeKind::BiOp(_, BiOpr::Divide, _));
+
+                let op = match op {
+                    BiOpr::Add => " + ",
+                    BiOpr::Subtract => " - ",
+                    BiOpr::Multiply => " ⋅ ",
+                    // keep numbers and fraction bars apart
+                    BiOpr::ImplicitMultiply
+                        if fraction(l) || fraction(r) || matches!(unwrap(r).kind, NodeKind::Number(_)) =>
+                    {
+                        " "
+                    },
+                    BiOpr::ImplicitMultiply => "",
+                    _ => " mod ",
+                };
+
+                self.operand(l, needs_brackets(node, Side::Left))
+                    .beside(Block::text(op))
+                    .beside(self.operand(r, needs_brackets(node, Side::Right)))
+            },
+            NodeKind::Function(c) => self.function(c),
+        }
+    }
+
+    fn function(&self, c: &Call<T>) -> Block {
+        let a = &c.args;
+
+        match (c.name.as_str(), a.len()) {
+            ("sqrt" | "√", 1) => radical(self.block(&a[0]), ' '),
+            ("cbrt" | "∛", 1) => radical(self.block(&a[0]), '3'),
+            ("abs", 1) => self.block(&a[0]).fenced(BAR, BAR),
+            ("floor", 1) => self.block(&a[0]).fenced(FLOOR.0, FLOOR.1),
+            ("ceil", 1) => self.block(&a[0]).fenced(CEIL.0, CEIL.1),
+            ("conj", 1) => {
+                let mut v = self.block(&a[0]);
+                v.lines.insert(0, "_".repeat(v.width));
+                v.baseline += 1;
+                v
+            },
+            ("log", 2) => {
+                let base = self.block(&a[1]);
+                let mut lines = vec![String::from("log") + &" ".repeat(base.width)];
+                lines.extend(base.lines.iter().map(|l| String::from("   ") + l));
+
+                Block {
+                    lines,
+                    width: 3 + base.width,
+                    baseline: 0,
+                }
+                .beside(self.block(&a[0]).fenced(ROUND.0, ROUND.1))
+            },
+            (name, _) => {
+                let mut args = Block::text("");
+
+                for (i, r) in a.iter().enumerate() {
+                    if i != 0 {
+                        args = args.beside(Block::text(", "));
+                    }
+
+                    args = args.beside(self.block(r));
+                }
+
+                Block::text(name).beside(args.fenced(ROUND.0, ROUND.1))
+            },
+        }
+    }
+
+    fn operand(&self, node: &Node<T>, brackets: bool) -> Block {
+        let v = self.block(node);
+
+        if !brackets {
+            return v;
+        }
+
+        let (open, close) = match &node.kind {
+            NodeKind::Group(BKind::Square, _) => SQUARE,
+            NodeKind::Group(BKind::Curly, _) => CURLY,
+            _ => ROUND,
+        };
+
+        v.fenced(open, close)
+    }
+}
+
+/// Lines of text that are all `width` characters wide, lined up with other blocks on the
+/// `baseline`th line
+struct Block {
+    lines: Vec<String>,
+    width: usize,
+    baseline: usize,
+}
+
+impl Block {
+    fn text(s: &str) -> Self {
+        Self {
+            lines: vec![s.into()],
+            width: s.chars().count(),
+            baseline: 0,
+        }
+    }
+
+    fn height(&self) -> usize { self.lines.len() }
+
+    /// Puts `right` after this block with the baselines lined up
+    fn beside(self, right: Block) -> Block {
+        let above = self.baseline.max(right.baseline);
+        let below = (self.height() - self.baseline).max(right.height() - right.baseline);
+        let (l, r) = (self.pad(above, below), right.pad(above, below));
+
+        Block {
+            lines: l.lines.into_iter().zip(r.lines).map(|(l, r)| l + &r).collect(),
+            width: l.width + r.width,
+            baseline: above,
+        }
+    }
+
+    /// Adds blank lines until there are `above` lines above the baseline and `below` lines from it
+    fn pad(mut self, above: usize, below: usize) -> Block {
+        let blank = " ".repeat(self.width);
+        let bottom = below - (self.height() - self.baseline);
+
+        self.lines.splice(0..0, core::iter::repeat_n(blank.clone(), above - self.baseline));
+        self.lines.extend(core::iter::repeat_n(blank, bottom));
+        self.baseline = above;
+        self
+    }
+
+    fn centre(mut self, width: usize) -> Block {
+        let left = (width - self.width) / 2;
+        let right = width - self.width - left;
+
+        for l in &mut self.lines {
+            *l = " ".repeat(left) + l + &" ".repeat(right);
+        }
+
+        self.width = width;
+        self
+    }
+
+    /// Puts brackets around the block that are as tall as it
+    fn fenced(self, open: Fence, close: Fence) -> Block {
+        let (h, baseline) = (self.height(), self.baseline);
+        fence(h, baseline, open).beside(self).beside(fence(h, baseline, close))
+    }
+}
+
+/// The characters of a bracket: on its own, at the top, in the middle, at the bottom and on the
+/// baseline
+type Fence = [char; 5];
+
+const ROUND: (Fence, Fence) = (['(', '⎛', '⎜', '⎝', '⎜'], [')', '⎞', '⎟', '⎠', '⎟']);
+const SQUARE: (Fence, Fence) = (['[', '⎡', '⎢', '⎣', '⎢'], [']', '⎤', '⎥', '⎦', '⎥']);
+const CURLY: (Fence, Fence) = (['{', '⎧', '⎪', '⎩', '⎨'], ['}', '⎫', '⎪', '⎭', '⎬']);
+const FLOOR: (Fence, Fence) = (['⌊', '⎢', '⎢', '⎣', '⎢'], ['⌋', '⎥', '⎥', '⎦', '⎥']);
+const CEIL: (Fence, Fence) = (['⌈', '⎡', '⎢', '⎢', '⎢'], ['⌉', '⎤', '⎥', '⎥', '⎥']);
+const BAR: Fence = ['|', '│', '│', '│', '│'];
+
+fn fence(height: usize, baseline: usize, [single, top, middle, bottom, centre]: Fence) -> Block {
+    let lines = (0..height)
+        .map(|i| match i {
+            _ if height == 1 => single,
+            0 => top,
+            i if i == height - 1 => bottom,
+            i if i == baseline => centre,
+            _ => middle,
+        })
+        .map(String::from)
+        .collect();
+
+    Block {
+        lines,
+        width: 1,
+        baseline,
+    }
+}
+
+/// A radical sign with a bar over `v` and `index` in the corner
+fn radical(v: Block, index: char) -> Block {
+    let h = v.height();
+    let mut lines = vec![String::from(index) + &" ".repeat(h) + &"_".repeat(v.width)];
+
+    for (i, l) in v.lines.iter().enumerate() {
+        let mut sign = vec![' '; h + 1];
+        sign[h - i] = '╱';
+
+        if i == h - 1 {
+            sign[0] = '╲';
+        }
+
+        lines.push(sign.into_iter().collect::<String>() + l);
+    }
+
+    Block {
+        lines,
+        width: h + 1 + v.width,
+        baseline: v.baseline + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn pretty(s: &str) -> String {
+        let node = crate::Expr::parse(s).unwrap();
+        PrettyDisplay { node: &node, src: s }.to_string()
+    }
+
+    #[test]
+    fn snapshots() {
+        for (s, expected) in [
+            ("1+2*3", "1 + 2 ⋅ 3"),
+            ("1/2", "1\n─\n2"),
+            ("(1+2)/34 + 5", "1 + 2\n───── + 5\n 34"),
+            ("2^3", " 3\n2"),
+            ("(1+2)^(1/2)", "       1\n       ─\n       2\n(1 + 2)"),
+            ("sqrt(2)", "  _\n╲╱2"),
+            ("cbrt(1/2)", "3   _\n   ╱1\n  ╱ ─\n╲╱  2"),
+            ("2(1/2 + 3)", " ⎛1    ⎞\n2⎜─ + 3⎟\n ⎝2    ⎠"),
+            ("abs(-1) + floor(½)", "|-1| + ⌊½⌋"),
+            ("log(8, 2)", "log (8)\n   2"),
+            ("max(1, 2π)", "max(1, 2π)"),
+        ] {
+            assert_eq!(pretty(s), expected, "{s}:\n{}", pretty(s));
+        }
+    }
+
+    #[test]
+    fn built() {
+        use crate::build::*;
+
+        for (n, expected) in [
+            (num::<f64>(-2.0).pow(num(2.0)), "    2\n(-2)"),
+            (num(2.0) * num(-3.0), "2 ⋅ (-3)"),
+            (-num(-1.5), "-(-1.5)"),
+        ] {
+            assert_eq!(PrettyDisplay { node: &n, src: "" }.to_string(), expected);
+        }
+    }
+}
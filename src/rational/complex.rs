@@ -18,7 +18,7 @@ impl<T: Clone + Integer + From<u8> + AddAssign + MulAssign> core::str::FromStr
             match (c, di) {
                 ('0'..='9', _) => {
                     numer *= 10.into();
-                    numer += (c as u8 - '0' as u8).into();
+                    numer += (c as u8 - b'0').into();
 
                     if di {
                         denom *= 10.into();
@@ -33,6 +33,62 @@ impl<T: Clone + Integer + From<u8> + AddAssign + MulAssign> core::str::FromStr
     }
 }
 
+impl<T: Clone + Integer + core::hash::Hash> NumberEq for ComplexRational<T> {
+    fn number_eq(&self, other: &Self) -> bool { self == other }
+
+    fn number_hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        core::hash::Hash::hash(&self.0.re, state);
+        core::hash::Hash::hash(&self.0.im, state);
+    }
+}
+
+/// The real and imaginary parts, written like [`Rational`]s
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(
+    rename = "ComplexRational",
+    bound(
+        serialize = "T: Clone + Integer + core::fmt::Display",
+        deserialize = "T: Clone + Integer + core::str::FromStr"
+    )
+)]
+struct Parts<T: Clone + Integer> {
+    re: Rational<T>,
+    im: Rational<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone + Integer + core::fmt::Display> serde::Serialize for ComplexRational<T> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Parts {
+            re: Rational(self.0.re.clone()),
+            im: Rational(self.0.im.clone()),
+        }
+        .serialize(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Clone + Integer + core::str::FromStr> serde::Deserialize<'de> for ComplexRational<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let Parts { re, im } = Parts::deserialize(d)?;
+        Ok(Self(Complex::new(re.0, im.0)))
+    }
+}
+
+/// Written as a [`Rational`], as `{im}i` or as `{re}+{im}i`
+impl<T: Clone + Integer> NumberForm for ComplexRational<T> {
+    fn is_negative(&self) -> bool {
+        match (self.0.re.is_zero(), self.0.im.is_zero()) {
+            (_, true) => Rational(self.0.re.clone()).is_negative(),
+            (true, false) => Rational(self.0.im.clone()).is_negative(),
+            (false, false) => false,
+        }
+    }
+
+    fn is_sum(&self) -> bool { !self.0.re.is_zero() && !self.0.im.is_zero() }
+}
+
 impl<T: Clone + Integer + From<usize>> FromConstant for ComplexRational<T> {
     fn from_constant(c: &str) -> Option<Self> {
         match c {
@@ -68,6 +124,16 @@ impl<T: Clone + Integer + From<u8> + AddAssign + MulAssign> Num for ComplexRatio
     }
 }
 
+impl<T: Clone + Integer + Signed + ToPrimitive + Pow<u64, Output = T>> BigPow for Complex<Ratio<T>> {
+    // `|re + im i| <= 2 max(|re|, |im|)`
+    fn approx_bits(&self) -> u64 {
+        let bits = self.re.approx_bits().max(self.im.approx_bits());
+        if self.re.is_zero() || self.im.is_zero() { bits } else { bits + 1 }
+    }
+
+    fn powu(self, exp: u64) -> Self { Pow::pow(&self, exp) }
+}
+
 impl<T: Clone + Integer> Zero for ComplexRational<T> {
     fn zero() -> Self { Self(Complex::zero()) }
 
@@ -87,6 +153,7 @@ impl<T: Clone + Integer> One for ComplexRational<T> {
 impl<
         T: Clone
             + Integer
+            + Roots
             + Zero
             + ToPrimitive
             + Signed
@@ -94,22 +161,50 @@ impl<
             + TryFrom<u64>
             + TryInto<u64>
             + Pow<u64, Output = T>,
-    > Pow<Self> for ComplexRational<T>
+    > CheckedOperation for ComplexRational<T>
 {
-    type Output = Self;
+    fn checked_rem(self, rhs: Self) -> Result<Self, &'static str> {
+        if rhs.is_zero() {
+            Err("division by zero")
+        } else {
+            Ok(self % rhs)
+        }
+    }
 
-    fn pow(self, exp: Self) -> Self {
+    fn checked_pow(self, exp: Self) -> Result<Self, &'static str> {
         if exp.is_zero() {
-            return Self::one();
+            return Ok(Self::one());
+        }
+
+        if self.is_zero() {
+            return if exp.0.re.is_positive() {
+                Ok(Self::zero())
+            } else {
+                Err("division by zero")
+            };
         }
 
-        (exp * self.ln()).exp()
+        if exp.0.im.is_zero() && exp.0.re.is_integer() {
+            let e = exp.0.re.to_integer();
+            let n = e.abs().to_u32().ok_or("result is too large")?;
+            let r = checked_powu(self.0, n as u64)?;
+            return Ok(Self(if e.is_negative() { r.inv() } else { r }));
+        }
+
+        if self.0.im.is_zero() && exp.0.im.is_zero() && exp.0.re.is_positive() && !exp.0.re.is_integer() {
+            if let Some(r) = Rational(self.0.re.clone()).exact_root(&Rational(exp.0.re.clone()))? {
+                return Ok(Self(Complex::new(r.0, Ratio::zero())));
+            }
+        }
+
+        (exp * self.ln()?).exp()
     }
 }
 
 impl<
         T: Clone
             + Integer
+            + Roots
             + Zero
             + ToPrimitive
             + Signed
@@ -119,9 +214,9 @@ impl<
             + Pow<u64, Output = T>,
     > ComplexRational<T>
 {
-    pub fn exp(self) -> Self {
+    pub fn exp(self) -> Result<Self, &'static str> {
         let Complex { re, im } = self.0;
-        Self::from_polar(Rational(re).exp().0, im)
+        Ok(Self::from_polar(Rational(re).exp()?.0, im))
     }
 
     pub fn from_polar(r: Ratio<T>, t: Ratio<T>) -> Self {
@@ -132,24 +227,24 @@ impl<
         Self(Complex::new(r.clone() * cos, r * sin))
     }
 
-    pub fn to_polar(self) -> (Ratio<T>, Ratio<T>) {
+    pub fn to_polar(self) -> Result<(Ratio<T>, Ratio<T>), &'static str> {
         let re = Rational(self.0.re);
         let im = Rational(self.0.im);
         let atan = im.atan2(&re);
         let re = re.0;
         let im = im.0;
 
-        (
+        Ok((
             Rational(re.clone() * re + im.clone() * im)
-                .pow(Rational(Ratio::new_raw(1.into(), 2.into())))
+                .checked_pow(Rational(Ratio::new_raw(1.into(), 2.into())))?
                 .0,
             atan.0,
-        )
+        ))
     }
 
-    pub fn ln(self) -> Self {
-        let (r, t) = self.to_polar();
-        Self(Complex::new(Rational(r).ln().unwrap().0, t))
+    pub fn ln(self) -> Result<Self, &'static str> {
+        let (r, t) = self.to_polar()?;
+        Ok(Self(Complex::new(Rational(r).ln()?.0, t)))
     }
 }
 
@@ -203,6 +298,7 @@ impl<T: Clone + Integer + Signed + core::fmt::Display + ToPrimitive> core::fmt::
 impl<
         T: Clone
             + Integer
+            + Roots
             + Zero
             + ToPrimitive
             + Signed
@@ -218,17 +314,21 @@ impl<
                 args[0].0.re.clone(),
                 -args[0].0.im.clone(),
             ))),
-            ("ln", 1) => Ok(args[0].clone().ln()),
-            ("exp", 1) => Ok(args[0].clone().exp()),
-            ("sqrt" | "√", 1) => Ok(args[0]
+            ("ln", 1) => args[0].clone().ln(),
+            ("exp", 1) => args[0].clone().exp(),
+            ("sqrt" | "√", 1) => args[0]
                 .clone()
-                .pow(Self(Complex::new(Ratio::new(T::one(), T::one() + T::one()), Ratio::zero())))),
-            ("cbrt" | "∛", 1) => Ok(args[0]
+                .checked_pow(Self(Complex::new(Ratio::new(T::one(), T::one() + T::one()), Ratio::zero()))),
+            ("cbrt" | "∛", 1) => args[0]
                 .clone()
-                .pow(Self(Complex::new(Ratio::new(T::one(), T::one() + T::one() + T::one()), Ratio::zero())))),
+                .checked_pow(Self(Complex::new(Ratio::new(T::one(), T::one() + T::one() + T::one()), Ratio::zero()))),
             _ => Err("function not supported"),
         }
     }
+
+    fn knows(f: &str, arity: usize) -> bool {
+        matches!((f, arity), ("conj" | "ln" | "exp" | "sqrt" | "√" | "cbrt" | "∛", 1))
+    }
 }
 
 impl<T: Clone + Integer> ComplexRational<T> {
@@ -4,22 +4,21 @@ use num_integer::*;
 use num_rational::*;
 use num_traits::*;
 
-mod ln_const;
-
 #[cfg(feature = "num_complex")]
 pub mod complex;
 
 impl<T: Clone + Integer> Rational<T> {
     pub fn limit_denom(&self, md: T) -> Self {
-        if md > self.0.denom().clone() {
+        if md >= self.0.denom().clone() {
             return self.clone();
         }
 
+        let neg = self.0.numer() < &T::zero();
         let mut p0 = T::zero();
         let mut q0 = T::one();
         let mut p1 = T::one();
         let mut q1 = T::zero();
-        let mut n = self.0.numer().clone();
+        let mut n = if neg { T::zero() - self.0.numer().clone() } else { self.0.numer().clone() };
         let mut d = self.0.denom().clone();
 
         loop {
@@ -43,11 +42,13 @@ impl<T: Clone + Integer> Rational<T> {
         let k = (md - q0.clone()) / q1.clone();
 
         let two = T::one() + T::one();
-        if two * d.clone() * (q0.clone() + k.clone() * q1.clone()) <= d {
-            Self(Ratio::new_raw(p1, q1))
+        let (p, q) = if two * d * (q0.clone() + k.clone() * q1.clone()) <= self.0.denom().clone() {
+            (p1, q1)
         } else {
-            Self(Ratio::new_raw(p0 + k.clone() * p1, q0 + k * q1))
-        }
+            (p0 + k.clone() * p1, q0 + k * q1)
+        };
+
+        Self(Ratio::new_raw(if neg { T::zero() - p } else { p }, q))
     }
 }
 
@@ -66,7 +67,7 @@ impl<T: Clone + Integer + From<u8> + AddAssign + MulAssign> core::str::FromStr f
             match (c, di) {
                 ('0'..='9', _) => {
                     numer *= 10.into();
-                    numer += (c as u8 - '0' as u8).into();
+                    numer += (c as u8 - b'0').into();
 
                     if di {
                         denom *= 10.into();
@@ -81,6 +82,17 @@ impl<T: Clone + Integer + From<u8> + AddAssign + MulAssign> core::str::FromStr f
     }
 }
 
+impl<T: Clone + Integer + core::hash::Hash> NumberEq for Rational<T> {
+    fn number_eq(&self, other: &Self) -> bool { self == other }
+
+    fn number_hash<H: core::hash::Hasher>(&self, state: &mut H) { core::hash::Hash::hash(&self.0, state) }
+}
+
+/// Written as an integer or as `(n / d)`
+impl<T: Clone + Integer> NumberForm for Rational<T> {
+    fn is_negative(&self) -> bool { self.0.denom().is_one() && self.0.numer() < &T::zero() }
+}
+
 impl<T: Clone + Integer + From<usize>> FromConstant for Rational<T> {
     fn from_constant(c: &str) -> Option<Self> {
         match c {
@@ -131,6 +143,7 @@ macro_rules! delegate_biop {
     };
 }
 
+#[cfg(feature = "num_complex")]
 use delegate_biop;
 
 delegate_biop!(Rational, Add, add);
@@ -175,115 +188,197 @@ impl<T: Clone + Integer> One for Rational<T> {
     fn set_one(&mut self) { self.0.set_one() }
 }
 
+/// The largest number of bits a numerator or denominator may get from exponentiation.
+const MAX_BITS: u64 = 1 << 20;
+
+/// Approximates the number of bits needed to store `v`.
+fn approx_bits<T: Clone + Integer + Signed + ToPrimitive + Pow<u64, Output = T>>(v: &T) -> u64 {
+    let mut v = v.abs();
+    let mut bits = 0;
+
+    loop {
+        match v.to_f64() {
+            Some(f) if f.is_finite() => {
+                return bits + ((f.to_bits() >> 52) & 0x7ff).saturating_sub(1022)
+            },
+            _ => {
+                // divide by the largest `2 ^ (512 * 2 ^ n)` that fits in `v`
+                let mut scale = (T::one() + T::one()).pow(512);
+                let mut scale_bits = 512;
+
+                while scale.clone() * scale.clone() <= v {
+                    scale = scale.clone() * scale;
+                    scale_bits *= 2;
+                }
+
+                v = v / scale;
+                bits += scale_bits;
+            },
+        }
+    }
+}
+
+/// Numbers whose size can be estimated before raising them to a power with [`checked_powu`].
+pub(crate) trait BigPow: Sized {
+    /// Approximates the number of bits needed to store the largest integer in `self`.
+    fn approx_bits(&self) -> u64;
+
+    fn powu(self, exp: u64) -> Self;
+}
+
+impl<T: Clone + Integer + Signed + ToPrimitive + Pow<u64, Output = T>> BigPow for Ratio<T> {
+    fn approx_bits(&self) -> u64 { approx_bits(self.numer()).max(approx_bits(self.denom())) }
+
+    fn powu(self, exp: u64) -> Self { self.pow(exp) }
+}
+
+/// Computes `base ^ exp` unless the result gets unreasonably large, every big integer power goes
+/// through here.
+pub(crate) fn checked_powu<B: BigPow>(base: B, exp: u64) -> Result<B, &'static str> {
+    let bits = base.approx_bits();
+
+    // powers of `0`, `1` and `-1` stay small
+    if bits > 1 && bits.saturating_mul(exp) > MAX_BITS {
+        Err("result is too large")
+    } else {
+        Ok(base.powu(exp))
+    }
+}
+
+/// Written as `numer/denom`, or `numer` if it is an integer, so big integers are kept exactly
+#[cfg(feature = "serde")]
+impl<T: Clone + Integer + core::fmt::Display> serde::Serialize for Rational<T> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if self.0.denom().is_one() {
+            s.collect_str(self.0.numer())
+        } else {
+            s.collect_str(&format_args!("{}/{}", self.0.numer(), self.0.denom()))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Clone + Integer + core::str::FromStr> serde::Deserialize<'de> for Rational<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = alloc::string::String::deserialize(d)?;
+        let invalid = || serde::de::Error::invalid_value(serde::de::Unexpected::Str(&s), &"a fraction such as `-1/2`");
+        let parse = |n: &str| n.parse::<T>().map_err(|_| invalid());
+
+        let (numer, denom) = match s.split_once('/') {
+            Some((n, d)) => (parse(n)?, parse(d)?),
+            None => (parse(&s)?, T::one()),
+        };
+
+        if denom.is_zero() {
+            return Err(invalid());
+        }
+
+        Ok(Self(Ratio::new(numer, denom)))
+    }
+}
+
 impl<
-        T: Clone + Integer + TryFrom<u64> + TryInto<u64> + Pow<u64, Output = T> + Signed + ToPrimitive,
-    > Pow<Self> for Rational<T>
+        T: Clone
+            + Integer
+            + Roots
+            + TryFrom<u64>
+            + TryInto<u64>
+            + Pow<u64, Output = T>
+            + Signed
+            + ToPrimitive,
+    > CheckedOperation for Rational<T>
 {
-    type Output = Self;
+    fn checked_rem(self, rhs: Self) -> Result<Self, &'static str> {
+        if rhs.is_zero() {
+            Err("division by zero")
+        } else {
+            Ok(self % rhs)
+        }
+    }
 
-    fn pow(self, exp: Self) -> Self {
+    fn checked_pow(self, exp: Self) -> Result<Self, &'static str> {
         if exp.0.is_negative() {
-            return Self((self.pow(Self(-exp.0))).0.inv());
+            if self.is_zero() {
+                return Err("division by zero");
+            }
+
+            return Ok(Self(self.checked_pow(Self(-exp.0))?.0.inv()));
         }
 
         if exp.is_zero() {
-            return Self::one();
+            return Ok(Self::one());
         }
 
         if exp.0.is_integer() {
-            return Self(Ratio::new(
-                self.0
-                    .numer()
-                    .clone()
-                    .pow(exp.0.to_integer().to_u64().unwrap()),
-                self.0
-                    .denom()
-                    .clone()
-                    .pow(exp.0.to_integer().to_u64().unwrap()),
-            ));
+            let exp = exp.0.to_integer().to_u64().ok_or("result is too large")?;
+            return Ok(Self(checked_powu(self.0, exp)?));
         }
 
         if exp.0.numer().is_odd() && exp.0.denom().is_even() && self.0.is_negative() {
-            panic!("invalid exp");
+            Err("even root of a negative number")
+        } else if let Some(r) = self.exact_root(&exp)? {
+            Ok(r)
         } else {
             let inv = exp.0.numer().is_odd() && exp.0.denom().is_odd() && self.0.is_negative();
-            let r = (Self(self.0.abs()).ln().unwrap() * exp).exp();
+            let r = (Self(self.0.abs()).ln()? * exp).exp()?;
 
-            if inv { -r } else { r }
+            Ok(if inv { -r } else { r })
         }
     }
 }
 
-impl<T: Clone + Integer + TryFrom<u64> + TryInto<u64> + Pow<u64, Output = T> + Signed> Rational<T> {
-    pub fn ln(self) -> Option<Self> {
+impl<
+        T: Clone
+            + Integer
+            + Roots
+            + TryFrom<u64>
+            + TryInto<u64>
+            + Pow<u64, Output = T>
+            + Signed
+            + ToPrimitive,
+    > Rational<T>
+{
+    pub fn ln(self) -> Result<Self, &'static str> {
         if !self.0.is_positive() {
-            return None;
+            return Err("logarithm of a non-positive number");
         }
 
-        let b = (self
-            .0
-            .round()
-            .numer()
-            .clone()
-            .min(ln_const::S.try_into().ok().unwrap())
-            - T::one())
-        .max(T::zero())
-        .try_into()
-        .ok()
-        .unwrap() as usize;
-        let consts = &ln_const::LN_CONSTS[b];
-        let b_a = Ratio::new(
-            consts[0].try_into().ok().unwrap(),
-            consts[1].try_into().ok().unwrap(),
-        );
-        let b_b = Ratio::new(
-            consts[2].try_into().ok().unwrap(),
-            consts[3].try_into().ok().unwrap(),
-        );
-        let b_c = Ratio::new(
-            consts[4].try_into().ok().unwrap(),
-            consts[5].try_into().ok().unwrap(),
-        );
+        // reduce to `self = 2^k * m` with `1 <= m < 2`
+        let two = Ratio::from(T::try_from(2).ok().unwrap());
+        let mut k = approx_bits(self.0.numer()) as i64 - approx_bits(self.0.denom()) as i64;
+        let scale = checked_powu(two.clone(), k.unsigned_abs())?;
+        let mut m = if k < 0 { self.0.clone() * scale } else { self.0.clone() / scale };
 
-        let p_a =
-            (b_a * ((self.0.clone() - T::one()) / (self.0.clone() + T::one()))).max(Ratio::zero());
-        let p = (p_a * T::try_from(ln_const::U).ok().unwrap()).round();
-
-        let mut x = (self.0.clone() / b_b.pow(p.numer().clone().try_into().ok().unwrap()))
-            + (p / T::try_from(ln_const::U).ok().unwrap()) * b_c
-            - T::one();
-
-        for _ in 0..(self.0.clone()
-            / T::try_from(ln_const::S).ok().unwrap())
-        .floor()
-        .numer()
-        .clone()
-        .try_into()
-        .ok()
-        .unwrap()
-        .min(8)
-        {
-            // #[cfg(debug_assertions)]
-            // println!("iter {i}");
-            let denom = (Ratio::from(T::try_from(1_000_000_000_000).ok().unwrap()) / x.clone().max(Ratio::one())).round().numer().clone().max(T::one());
-            let exp = Self(x.clone()).limit_denom(denom.clone()).exp();
-            let denom = (Ratio::from(T::try_from(1_000_000_000_000).ok().unwrap()) / exp.0.clone().max(Ratio::one())).round().numer().clone().max(T::one());
-            let exp = exp.limit_denom(denom).0;
-            x = x - (exp.clone() - self.0.clone()) / exp;
+        while m >= two {
+            m = m / two.clone();
+            k += 1;
         }
 
-        Some(Self(x).limit_denom(1_000_000_000_000.try_into().ok().unwrap()))
+        while m < Ratio::one() {
+            m = m * two.clone();
+            k -= 1;
+        }
+
+        // ln(2) = ln((1 + 1/3) / (1 - 1/3)) and ln(m) = ln((1 + z) / (1 - z)) with 0 <= z < 1/3
+        let ln_2 = ln_series(Ratio::new(T::one(), T::try_from(3).ok().unwrap()));
+        let ln_m = ln_series((m.clone() - T::one()) / (m + T::one()));
+        let ln_2k = ln_2 * T::try_from(k.unsigned_abs()).ok().unwrap();
+        let ln_2k = if k < 0 { -ln_2k } else { ln_2k };
+
+        Ok(Self(ln_2k + ln_m).limit_denom(1_000_000_000_000.try_into().ok().unwrap()))
     }
 
-    pub fn exp(self) -> Self {
-        Self(
-            Ratio::new_raw(
-                517656.try_into().ok().unwrap(),
-                190435.try_into().ok().unwrap(),
-            )
-            .pow(self.0.floor().numer().clone().try_into().ok().unwrap())
-                * exp_corr(self.0.fract()),
-        )
+    pub fn exp(self) -> Result<Self, &'static str> {
+        let e = Ratio::new_raw(
+            517656.try_into().ok().unwrap(),
+            190435.try_into().ok().unwrap(),
+        );
+        let floor = self.0.floor().to_integer();
+        let n = floor.abs().to_u64().ok_or("result is too large")?;
+        let e_n = checked_powu(e, n)?;
+        let e_n = if floor.is_negative() { e_n.inv() } else { e_n };
+
+        Ok(Self(e_n * exp_corr(self.0.fract())))
     }
 
     pub fn sin(&self) -> Self {
@@ -322,8 +417,9 @@ impl<T: Clone + Integer + TryFrom<u64> + TryInto<u64> + Pow<u64, Output = T> + S
         Self(self.0.clone() + halfpi).sin()
     }
 
-    pub fn tan(&self) -> Self {
-        self.sin() / self.cos()
+    pub fn tan(&self) -> Option<Self> {
+        let cos = self.cos();
+        (!cos.is_zero()).then(|| self.sin() / cos)
         // let pi = Ratio::new_raw(
         //     312689.try_into().ok().unwrap(),
         //     99532.try_into().ok().unwrap(),
@@ -377,6 +473,62 @@ impl<T: Clone + Integer + TryFrom<u64> + TryInto<u64> + Pow<u64, Output = T> + S
     }
 }
 
+impl<T: Clone + Integer + Roots + Pow<u64, Output = T> + Signed + ToPrimitive> Rational<T> {
+    /// Computes `self ^ exp` exactly if the result is rational, i.e. if both the numerator and the
+    /// denominator of `self` are perfect powers of the denominator of `exp`.
+    ///
+    /// `exp` is expected to be positive and not an integer. Fails if the result would be too large.
+    pub fn exact_root(&self, exp: &Self) -> Result<Option<Self>, &'static str> {
+        let (Some(n), Some(p)) = (exp.0.denom().to_u32(), exp.0.numer().to_u64()) else {
+            return Ok(None);
+        };
+
+        if self.0.is_negative() && n % 2 == 0 {
+            return Ok(None);
+        }
+
+        let numer = self.0.numer().abs();
+        let denom = self.0.denom().abs();
+        let rn = numer.nth_root(n);
+        let rd = denom.nth_root(n);
+
+        let r = Ratio::new_raw(rn, rd);
+
+        if checked_powu(r.clone(), n as u64)? != Ratio::new_raw(numer, denom) {
+            return Ok(None);
+        }
+
+        let r = checked_powu(r, p)?;
+        Ok(Some(Self(if self.0.is_negative() && p % 2 == 1 { -r } else { r })))
+    }
+}
+
+/// Limit of the denominators of intermediate results in [`ln_series`]
+const LN_PRECISION: u64 = 1_000_000_000_000_000_000;
+
+/// Computes `ln((1 + z) / (1 - z)) = 2 atanh(z)` for `|z| < 1` with the series
+/// `2 (z + z^3 / 3 + z^5 / 5 + ...)`.
+fn ln_series<T: Clone + Integer + TryFrom<u64> + Signed>(z: Ratio<T>) -> Ratio<T> {
+    let precision = T::try_from(LN_PRECISION).ok().unwrap();
+    let limit = |r: Ratio<T>| Rational(r).limit_denom(precision.clone()).0;
+
+    let z = limit(z);
+    let z2 = limit(z.clone() * z.clone());
+    let two = T::one() + T::one();
+
+    let mut term = z;
+    let mut sum = Ratio::zero();
+    let mut n = T::one();
+
+    while !term.is_zero() {
+        sum = limit(sum + term.clone() / n.clone());
+        term = limit(term * z2.clone());
+        n = n + two.clone();
+    }
+
+    sum * two
+}
+
 fn exp_corr<T: Clone + Integer + TryFrom<u64> + Pow<u64, Output = T>>(r: Ratio<T>) -> Ratio<T> {
     let two = T::try_from(2_u64).ok().unwrap();
     let six = T::try_from(6_u64).ok().unwrap();
@@ -402,7 +554,14 @@ impl<T: Clone + Integer + core::fmt::Display + ToPrimitive> core::fmt::Display f
 }
 
 impl<
-        T: Clone + Integer + TryFrom<u64> + TryInto<u64> + Pow<u64, Output = T> + Signed + ToPrimitive,
+        T: Clone
+            + Integer
+            + Roots
+            + TryFrom<u64>
+            + TryInto<u64>
+            + Pow<u64, Output = T>
+            + Signed
+            + ToPrimitive,
     > ExecuteFunction for Rational<T>
 {
     fn execute(f: &str, args: &[Self]) -> Result<Self, &'static str> {
@@ -413,20 +572,20 @@ impl<
             ("trunc", 1) => Ok(Self(args[0].0.trunc())),
             ("fract", 1) => Ok(Self(args[0].0.fract())),
             ("abs", 1) => Ok(Self(args[0].0.abs())),
-            ("sqrt" | "√", 1) => Ok(args[0]
+            ("sqrt" | "√", 1) => args[0]
                 .clone()
-                .pow(Self(Ratio::new(T::one(), T::one() + T::one())))),
-            ("cbrt" | "∛", 1) => Ok(args[0]
+                .checked_pow(Self(Ratio::new(T::one(), T::one() + T::one()))),
+            ("cbrt" | "∛", 1) => args[0]
                 .clone()
-                .pow(Self(Ratio::new(T::one(), T::one() + T::one() + T::one())))),
-            ("ln", 1) => args[0].clone().ln().ok_or("`ln` math error"),
+                .checked_pow(Self(Ratio::new(T::one(), T::one() + T::one() + T::one()))),
+            ("ln", 1) => args[0].clone().ln(),
             // ("log", 1) => Ok(Self(from_f64!(to_f64!(args[0].0).log10()))),
             // ("log", 2) => Ok(Self(from_f64!(to_f64!(args[0].0).log(to_f64!(args[0].0))))),
             ("min", _) => Ok(Self(args.iter().map(|a| a.0.clone()).min().ok_or("expected ≥1 arguments")?)),
             ("max", _) => Ok(Self(args.iter().map(|a| a.0.clone()).max().ok_or("expected ≥1 arguments")?)),
             ("sin", 1) => Ok(args[0].sin()),
             ("cos", 1) => Ok(args[0].cos()),
-            ("tan", 1) => Ok(args[0].tan()),
+            ("tan", 1) => args[0].tan().ok_or("`tan` math error"),
             // ("arcsin", 1) => Ok(Self(from_f64!(to_f64!(args[0].0).asin()))),
             // ("arccos", 1) => Ok(Self(from_f64!(to_f64!(args[0].0).acos()))),
             ("arctan", 1) => Ok(args[0].atan()),
@@ -440,4 +599,54 @@ impl<
             _ => Err("function not supported"),
         }
     }
+
+    fn knows(f: &str, arity: usize) -> bool {
+        matches!(
+            (f, arity),
+            ("floor" | "ceil" | "round" | "trunc" | "fract" | "abs" | "sqrt" | "√" | "cbrt" | "∛" | "ln", 1)
+                | ("min" | "max", _)
+                | ("sin" | "cos" | "tan" | "arctan", 1)
+                | ("arctan2", 2)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    fn eval(s: &str) -> Rational<BigInt> { crate::to_nodes(s).unwrap().evaluate().unwrap() }
+
+    fn rat(n: i64, d: i64) -> Rational<BigInt> { Rational(Ratio::new(n.into(), d.into())) }
+
+    #[test]
+    fn exact_roots() {
+        assert_eq!(eval("sqrt(9/4)"), rat(3, 2));
+        assert_eq!(eval("cbrt(-27/8)"), rat(-3, 2));
+        assert_eq!(eval("(16/81)^(3/4)"), rat(8, 27));
+        assert_eq!(eval("(-8)^(2/3)"), rat(4, 1));
+        assert_eq!(eval("4^(-1/2)"), rat(1, 2));
+        assert_ne!(eval("sqrt(2)").0.denom(), &BigInt::one());
+        assert!(crate::to_nodes::<Rational<BigInt>>("4^(99999999999/2)").unwrap().evaluate().is_err());
+        assert_eq!(eval("(-1)^99999999999"), rat(-1, 1));
+        assert_eq!(eval("1^(99999999999/2)"), rat(1, 1));
+    }
+
+    #[test]
+    fn ln_accuracy() {
+        for (s, expected) in [
+            ("ln(1.4)", 1.4_f64.ln()),
+            ("ln(2)", 2_f64.ln()),
+            ("ln(1/3)", (1.0_f64 / 3.0).ln()),
+            ("ln(100000)", 100000_f64.ln()),
+            ("ln(10^100)", 100.0 * 10_f64.ln()),
+            ("ln(10^(-30))", -30.0 * 10_f64.ln()),
+            ("ln(10^10000)", 10000.0 * 10_f64.ln()),
+        ] {
+            let r = eval(s).0;
+            let r = r.numer().to_f64().unwrap() / r.denom().to_f64().unwrap();
+            assert!((r - expected).abs() <= 1e-12 * expected.abs().max(1.0), "{s} = {r}");
+        }
+    }
 }
@@ -0,0 +1,353 @@
+//! Rewriting trees with rules such as `sin(a)^2 + cos(a)^2 -> 1`.
+//!
+//! Every name in a rule that is not a constant such as `π` is a pattern variable, which matches
+//! any subtree. A variable that appears more than once only matches equal subtrees.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use crate::*;
+use crate::latex::unwrap;
+use crate::visit::{walk_call_mut, walk_node_mut, Fold, MutVisitor, Visitor};
+
+/// Replaces the trees that match `pattern` with `replacement`
+#[derive(Debug, Clone)]
+pub struct Rule<N> {
+    pub pattern: Node<N>,
+    pub replacement: Node<N>,
+}
+
+impl<N: Clone + Numeral> Rule<N> {
+    /// Parses a rule written as `pattern -> replacement`. A name is a run of letters, so `ab` is
+    /// one variable and `a b` is a product. The nodes have empty spans, like the ones from
+    /// [`build`].
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let Some(arrow) = s.find("->") else {
+            return Err(Error {
+                kind: ErrorKind::Syntax,
+                message: "expected `->` between the pattern and the replacement".into(),
+                location: 0..s.len(),
+            });
+        };
+
+        let side = |src: &str, offset: usize| {
+            let mut arena = Arena::growable();
+            let root = arena.parse_with_variables(src).map_err(|e| Error {
+                location: e.location.start + offset..e.location.end + offset,
+                ..e
+            })?;
+
+            arena.to_node::<N>(root)
+        };
+
+        let mut pattern = side(&s[..arrow], 0)?;
+        let mut replacement = side(&s[arrow + 2..], arrow + 2)?;
+
+        let mut variables = Variables(Vec::new());
+        variables.visit_node(&pattern);
+        let known = variables.0;
+
+        let mut variables = Variables(Vec::new());
+        variables.visit_node(&replacement);
+        if let Some((_, span)) = variables.0.into_iter().find(|(v, _)| !known.iter().any(|(k, _)| k == v)) {
+            return Err(Error {
+                kind: ErrorKind::Syntax,
+                message: "this variable is not in the pattern".into(),
+                location: span.start + arrow + 2..span.end + arrow + 2,
+            });
+        }
+
+        Unspan.visit_node_mut(&mut pattern);
+        Unspan.visit_node_mut(&mut replacement);
+
+        Ok(Self { pattern, replacement })
+    }
+}
+
+/// Applies rules until none of them matches
+#[derive(Debug, Clone)]
+pub struct Rewriter<N> {
+    pub rules: Vec<Rule<N>>,
+    /// Match sums and products in any order and grouping, e.g. `a + 0` also matches a part of
+    /// `0 + x + y`
+    pub commutative: bool,
+    /// The most rewrites that are made, rules such as `a * b -> b * a` never stop by themselves
+    pub max_steps: usize,
+}
+
+impl<N> Rewriter<N> {
+    pub fn new(rules: Vec<Rule<N>>) -> Self {
+        Self {
+            rules,
+            commutative: false,
+            max_steps: 1000,
+        }
+    }
+}
+
+/// The subtrees that pattern variables matched
+type Bindings<N> = Vec<(String, Node<N>)>;
+
+impl<N: Clone + NumberEq> Rewriter<N> {
+    /// Rewrites `node` until no rule matches. The rules are tried in order on every node, outer
+    /// nodes before inner ones.
+    pub fn apply(&self, node: &Node<N>) -> Result<Node<N>, Error> {
+        let mut out = node.clone();
+        let mut steps = 0;
+
+        while let Some(n) = self.step(&out) {
+            if steps == self.max_steps {
+                return Err(Error {
+                    kind: ErrorKind::Evaluation,
+                    message: "the rules did not stop rewriting".into(),
+                    location: node.span.clone(),
+                });
+            }
+
+            out = n;
+            steps += 1;
+        }
+
+        Ok(out)
+    }
+
+    /// Makes one rewrite
+    fn step(&self, node: &Node<N>) -> Option<Node<N>> {
+        if let Some(n) = self.rules.iter().find_map(|r| self.rewrite(r, node)) {
+            return Some(n);
+        }
+
+        let kind = match &node.kind {
+            NodeKind::Number(_) | NodeKind::Constant(_) => return None,
+            NodeKind::BiOp(l, op, r) => match self.step(l) {
+                Some(l) => NodeKind::BiOp(Box::new(l), *op, r.clone()),
+                None => NodeKind::BiOp(l.clone(), *op, Box::new(self.step(r)?)),
+            },
+            NodeKind::UnOp(op, v) => NodeKind::UnOp(*op, Box::new(self.step(v)?)),
+            NodeKind::Group(k, v) => NodeKind::Group(*k, Box::new(self.step(v)?)),
+            NodeKind::Function(c) => {
+                let (i, a) = c.args.iter().enumerate().find_map(|(i, a)| Some((i, self.step(a)?)))?;
+                let mut c = c.clone();
+                c.args[i] = a;
+                NodeKind::Function(c)
+            },
+        };
+
+        Some(Node {
+            kind,
+            span: node.span.clone(),
+        })
+    }
+
+    fn rewrite(&self, rule: &Rule<N>, node: &Node<N>) -> Option<Node<N>> {
+        let mut b = Vec::new();
+
+        let chain = match unwrap(&rule.pattern).kind {
+            NodeKind::BiOp(_, op, _) if self.commutative => class(op),
+            _ => None,
+        };
+
+        let Some(op) = chain else {
+            return self.matches(&rule.pattern, node, &mut b).then(|| bind(&rule.replacement, &b));
+        };
+
+        // the pattern may match only some of the operands, the others are kept
+        let (ps, ts) = (flatten(&rule.pattern, op), flatten(node, op));
+        let mut used = Vec::new();
+
+        if ps.len() > ts.len() || !self.match_all(&ps, &ts, &mut used, &mut b) {
+            return None;
+        }
+
+        let first = used.iter().min().copied();
+        let replacement = bind(&rule.replacement, &b);
+
+        (0..ts.len())
+            .filter_map(|i| match i {
+                _ if Some(i) == first => Some(replacement.clone()),
+                _ if used.contains(&i) => None,
+                _ => Some(ts[i].clone()),
+            })
+            .reduce(|l, r| Node {
+                kind: NodeKind::BiOp(Box::new(l), op, Box::new(r)),
+                span: 0..0,
+            })
+    }
+
+    fn matches(&self, p: &Node<N>, n: &Node<N>, b: &mut Bindings<N>) -> bool {
+        let (p, n) = (unwrap(p), unwrap(n));
+
+        match (&p.kind, &n.kind) {
+            (NodeKind::Constant(v), _) if !is_constant(v) => match b.iter().find(|(name, _)| name == v) {
+                Some((_, bound)) => bound == n,
+                None => {
+                    b.push((v.clone(), n.clone()));
+                    true
+                },
+            },
+            (NodeKind::Number(a), NodeKind::Number(c)) => a.number_eq(c),
+            (NodeKind::Constant(a), NodeKind::Constant(c)) => a == c,
+            (NodeKind::UnOp(o, a), NodeKind::UnOp(q, c)) => o == q && self.matches(a, c, b),
+            (NodeKind::BiOp(_, o, _), NodeKind::BiOp(_, q, _)) if self.commutative && same_chain(*o, *q) => {
+                let op = class(*o).unwrap();
+                let (ps, ts) = (flatten(p, op), flatten(n, op));
+                ps.len() == ts.len() && self.match_all(&ps, &ts, &mut Vec::new(), b)
+            },
+            (NodeKind::BiOp(pl, o, pr), NodeKind::BiOp(l, q, r)) => {
+                (o == q || same_chain(*o, *q)) && self.matches(pl, l, b) && self.matches(pr, r, b)
+            },
+            (NodeKind::Function(f), NodeKind::Function(g)) => {
+                f.name == g.name
+                    && f.args.len() == g.args.len()
+                    && f.args.iter().zip(&g.args).all(|(p, n)| self.matches(p, n, b))
+            },
+            _ => false,
+        }
+    }
+
+    /// Matches every pattern in `ps` with a different node of `ts`, `used` are the indices of the
+    /// nodes that are matched
+    fn match_all(&self, ps: &[&Node<N>], ts: &[&Node<N>], used: &mut Vec<usize>, b: &mut Bindings<N>) -> bool {
+        let Some((p, rest)) = ps.split_first() else {
+            return true;
+        };
+
+        for (i, t) in ts.iter().enumerate() {
+            if used.contains(&i) {
+                continue;
+            }
+
+            let len = b.len();
+            used.push(i);
+
+            if self.matches(p, t, b) && self.match_all(rest, ts, used, b) {
+                return true;
+            }
+
+            used.pop();
+            b.truncate(len);
+        }
+
+        false
+    }
+}
+
+/// The operator of a chain that can be reordered, both multiplications are the same
+fn class(op: BiOpr) -> Option<BiOpr> {
+    match op {
+        BiOpr::Add => Some(BiOpr::Add),
+        BiOpr::Multiply | BiOpr::ImplicitMultiply => Some(BiOpr::Multiply),
+        _ => None,
+    }
+}
+
+fn same_chain(a: BiOpr, b: BiOpr) -> bool { class(a).is_some_and(|c| class(b) == Some(c)) }
+
+/// The operands of a chain of `op`, e.g. `1`, `x` and `2` in `(1 + x) + 2`
+fn flatten<N>(node: &Node<N>, op: BiOpr) -> Vec<&Node<N>> {
+    let node = unwrap(node);
+
+    match &node.kind {
+        NodeKind::BiOp(l, o, r) if class(*o) == Some(op) => {
+            let mut v = flatten(l, op);
+            v.extend(flatten(r, op));
+            v
+        },
+        _ => Vec::from([node]),
+    }
+}
+
+fn bind<N: Clone>(replacement: &Node<N>, b: &Bindings<N>) -> Node<N> { Bind(b).fold_node(replacement.clone()) }
+
+struct Bind<'a, N>(&'a Bindings<N>);
+
+impl<N: Clone> Fold<N> for Bind<'_, N> {
+    fn fold_constant(&mut self, c: String, span: Span) -> Node<N> {
+        match self.0.iter().find(|(v, _)| *v == c) {
+            Some((_, n)) => n.clone(),
+            None => Node {
+                kind: NodeKind::Constant(c),
+                span,
+            },
+        }
+    }
+}
+
+/// Collects the pattern variables of a tree
+struct Variables<'a>(Vec<(&'a str, &'a Span)>);
+
+impl<'a, N> Visitor<'a, N> for Variables<'a> {
+    fn visit_constant(&mut self, c: &'a str, span: &'a Span) {
+        if !is_constant(c) {
+            self.0.push((c, span));
+        }
+    }
+}
+
+/// Empties every span of a tree
+struct Unspan;
+
+impl<N> MutVisitor<N> for Unspan {
+    fn visit_node_mut(&mut self, node: &mut Node<N>) {
+        node.span = 0..0;
+        walk_node_mut(self, node);
+    }
+
+    fn visit_call_mut(&mut self, c: &mut Call<N>, _span: &mut Span) {
+        c.name_span = 0..0;
+        c.args_span = 0..0;
+        walk_call_mut(self, c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::*;
+    use alloc::{string::ToString, vec};
+
+    fn rewriter(rules: &[&str]) -> Rewriter<f64> { Rewriter::new(rules.iter().map(|r| Rule::parse(r).unwrap()).collect()) }
+
+    #[test]
+    fn parse() {
+        let r = Rule::<f64>::parse("sin(a)^2 + cos(a)^2 -> 1").unwrap();
+        assert_eq!(r.pattern, func("sin", [var("a")]).pow(num(2)) + func("cos", [var("a")]).pow(num(2)));
+        assert_eq!(r.replacement, num(1));
+        assert_eq!(r.replacement.span, 0..0);
+
+        let r = Rule::<f64>::parse("2ab -> π ab").unwrap();
+        assert_eq!(r.pattern.to_string(), "2ab");
+        assert_eq!(r.replacement.to_string(), "π ab");
+
+        assert_eq!(Rule::<f64>::parse("a + b").unwrap_err().location, 0..5);
+        assert_eq!(Rule::<f64>::parse("log(a) -> b").unwrap_err().location, 10..11);
+        assert_eq!(Rule::<f64>::parse("a -> )").unwrap_err().location, 5..6);
+    }
+
+    #[test]
+    fn apply() {
+        let x = || var::<f64>("x");
+        let y = || var::<f64>("y");
+
+        let r = rewriter(&["log(a*b) -> log(a) + log(b)"]);
+        let n = func("log", [x() * y() * func("sin", [x()])]);
+        assert_eq!(r.apply(&n).unwrap().to_string(), "log(x) + log(y) + log(sin(x))");
+
+        let r = rewriter(&["a - a -> 0", "a * 1 -> a"]);
+        assert_eq!(r.apply(&((x() + num(1)) * num(1) - (x() + num(1)))).unwrap(), num(0));
+        assert_eq!(r.apply(&(x() - y())).unwrap(), x() - y());
+
+        let mut r = rewriter(&["sin(a)^2 + cos(a)^2 -> 1"]);
+        let n = func("cos", [x()]).pow(num(2)) + num(2) + func("sin", [x()]).pow(num(2));
+        assert_eq!(r.apply(&n).unwrap(), n);
+        r.commutative = true;
+        assert_eq!(r.apply(&n).unwrap().to_string(), "1 + 2");
+
+        let n = to_nodes::<f64>("3(cos(π)^2 + sin(π)^2)").unwrap();
+        assert_eq!(r.apply(&n).unwrap().to_string(), "3 1");
+
+        let mut r = rewriter(&["a * b -> b * a"]);
+        r.max_steps = 10;
+        assert_eq!(r.apply(&(x() * y())).unwrap_err().message, "the rules did not stop rewriting");
+        r.rules = vec![];
+        assert_eq!(r.apply(&(x() * y())).unwrap(), x() * y());
+    }
+}
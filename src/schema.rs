@@ -0,0 +1,116 @@
+//! The format of serialized trees, errors and numbers.
+//!
+//! Values that are stored or sent somewhere should be wrapped in [`Versioned`], so that a reader
+//! rejects them cleanly once the format has changed.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The version of the format that is written and read. It changes whenever a serialized type
+/// changes in a way that older readers would misread.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    #[serde(deserialize_with = "version")]
+    pub version: u32,
+    pub value: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            version: VERSION,
+            value,
+        }
+    }
+}
+
+fn version<'de, D: Deserializer<'de>>(d: D) -> Result<u32, D::Error> {
+    let v = u32::deserialize(d)?;
+
+    if v == VERSION {
+        Ok(v)
+    } else {
+        Err(serde::de::Error::custom(format_args!("unsupported schema version {v}, expected {VERSION}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use alloc::string::ToString;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(v: T) -> (T, alloc::string::String) {
+        let json = serde_json::to_string(&Versioned::new(v)).unwrap();
+        let back: Versioned<T> = serde_json::from_str(&json).unwrap();
+        (back.value, json)
+    }
+
+    #[test]
+    fn expr() {
+        let (e, json) = round_trip(Expr::parse("1+2").unwrap());
+        assert_eq!(
+            json,
+            r#"{"version":1,"value":{"kind":{"BiOp":[{"kind":{"Number":"1"},"span":{"start":0,"end":1}},"Add",{"kind":{"Number":"2"},"span":{"start":2,"end":3}}]},"span":{"start":0,"end":3}}}"#
+        );
+        assert!(e.eq_spanned(&Expr::parse("1+2").unwrap()));
+
+        let s = "-2^(1/3) + max[π, 1.50] % ∛(8)";
+        let (e, _) = round_trip(Expr::parse(s).unwrap());
+        assert!(e.eq_spanned(&Expr::parse(s).unwrap()));
+
+        let (n, _) = round_trip(to_nodes::<f64>(s).unwrap());
+        assert!(n.eq_spanned(&to_nodes::<f64>(s).unwrap()));
+    }
+
+    #[test]
+    fn error() {
+        let e = to_nodes::<f64>("1 + )").unwrap_err();
+        let (back, json) = round_trip(e.clone());
+        assert_eq!(json, r#"{"version":1,"value":{"kind":"Syntax","message":"did not expect this","location":{"start":4,"end":5}}}"#);
+        assert_eq!((back.kind, back.message, back.location), (e.kind, e.message, e.location));
+    }
+
+    #[test]
+    fn version() {
+        let json = r#"{"version":2,"value":{"kind":{"Number":"1"},"span":{"start":0,"end":1}}}"#;
+        let e = serde_json::from_str::<Versioned<Expr>>(json).unwrap_err();
+        assert!(e.to_string().starts_with("unsupported schema version 2"), "{e}");
+    }
+
+    #[cfg(feature = "num_rational")]
+    #[test]
+    fn rational() {
+        use num_bigint::BigInt;
+        use num_rational::Ratio;
+        use rational::Rational;
+
+        let big: BigInt = "-123456789012345678901234567890123456789".parse().unwrap();
+        let (r, json) = round_trip(Rational(Ratio::new(big.clone(), 7.into())));
+        assert_eq!(json, r#"{"version":1,"value":"-123456789012345678901234567890123456789/7"}"#);
+        assert_eq!(r.0, Ratio::new(big.clone(), 7.into()));
+
+        let (r, json) = round_trip(Rational(Ratio::from(big.clone())));
+        assert_eq!(json, r#"{"version":1,"value":"-123456789012345678901234567890123456789"}"#);
+        assert_eq!(r.0, Ratio::from(big));
+
+        for invalid in [r#""1/0""#, r#""1.5""#, r#""""#, "1"] {
+            assert!(serde_json::from_str::<Rational<BigInt>>(invalid).is_err(), "{invalid}");
+        }
+
+        let n = to_nodes::<Rational<BigInt>>("1.25 * 3").unwrap();
+        let (back, _) = round_trip(n.clone());
+        assert!(back.eq_spanned(&n));
+
+        #[cfg(feature = "num_complex")]
+        {
+            use rational::complex::ComplexRational;
+
+            let c = ComplexRational::<BigInt>(num_complex::Complex::new(Ratio::new(1.into(), 2.into()), Ratio::from(BigInt::from(-3))));
+            let (back, json) = round_trip(c.clone());
+            assert_eq!(json, r#"{"version":1,"value":{"re":"1/2","im":"-3"}}"#);
+            assert_eq!(back, c);
+        }
+    }
+}
@@ -0,0 +1,600 @@
+//! Algebraic simplification, e.g. `2x + 3x` becomes `5x` and `x^2 x^3` becomes `x^5`.
+//!
+//! Numbers are combined in the number type of the tree, so a tree of
+//! `rational::Rational`s is simplified exactly. Functions are not evaluated
+//! and named constants such as `π` are kept like variables. Terms with `∞` or a number that is not
+//! finite are never cancelled, e.g. `∞ - ∞` stays as it is.
+
+use core::{cmp::Ordering, slice};
+use alloc::{boxed::Box, vec, vec::Vec};
+use crate::*;
+
+const SPAN: Span = 0..0;
+
+/// The result of [`Node::simplify`]
+#[derive(Debug, Clone)]
+pub struct Simplified<N> {
+    /// A tree with empty spans, like the ones from [`build`]
+    pub node: Node<N>,
+    /// Expressions that must not be zero for `node` to equal the original tree, e.g. `x` when
+    /// `x/x` became `1`
+    pub conditions: Vec<Node<N>>,
+}
+
+/// `base^exponent`
+#[derive(Clone)]
+struct Factor<N> {
+    base: Node<N>,
+    exponent: N,
+}
+
+/// `coefficient * factors`, the bases of the factors are distinct and the coefficient is not zero
+#[derive(Clone)]
+struct Term<N> {
+    coefficient: N,
+    factors: Vec<Factor<N>>,
+}
+
+/// Terms with distinct factors, zero is the empty sum
+type Sum<N> = Vec<Term<N>>;
+
+struct Simplifier<N> {
+    conditions: Vec<Node<N>>,
+}
+
+impl<N: ComputableNumeral + NumberEq + NumberForm> Node<N> {
+    /// Collects like terms, combines numbers, cancels common factors of fractions, applies the
+    /// power laws that hold for integer exponents and sorts terms and factors
+    pub fn simplify(&self) -> Result<Simplified<N>, Error> {
+        let mut s = Simplifier { conditions: Vec::new() };
+        let sum = s.sum(self)?;
+
+        Ok(Simplified {
+            node: render(&sum),
+            conditions: s.conditions,
+        })
+    }
+}
+
+impl<N: ComputableNumeral + NumberEq + NumberForm> Simplifier<N> {
+    fn sum(&mut self, node: &Node<N>) -> Result<Sum<N>, Error> {
+        let error = |message: &'static str| Error {
+            kind: ErrorKind::Evaluation,
+            message: message.into(),
+            location: node.span.clone(),
+        };
+
+        Ok(match &node.kind {
+            NodeKind::Number(n) => constant(n.clone()),
+            // vulgar fractions
+            NodeKind::Constant(c) if !c.chars().any(char::is_alphabetic) && c != "∞" => {
+                constant(arena::constant(c, &node.span)?)
+            },
+            NodeKind::Constant(c) => atom(leaf(NodeKind::Constant(c.clone()))),
+            NodeKind::Group(_, v) | NodeKind::UnOp(UnOpr::Plus, v) => self.sum(v)?,
+            NodeKind::UnOp(UnOpr::Minus, v) => negate(self.sum(v)?),
+            NodeKind::BiOp(l, op, r) => {
+                let (l, r) = (self.sum(l)?, self.sum(r)?);
+
+                match op {
+                    BiOpr::Add => self.add(l, r),
+                    BiOpr::Subtract => self.add(l, negate(r)),
+                    BiOpr::Multiply | BiOpr::ImplicitMultiply => self.mul(l, r).map_err(error)?,
+                    BiOpr::Divide => {
+                        let r = self.inverse(r).map_err(error)?;
+                        self.mul(l, r).map_err(error)?
+                    },
+                    BiOpr::Power => self.pow(l, r).map_err(error)?,
+                    BiOpr::Modulo => match (as_constant(&l), as_constant(&r)) {
+                        (Some(a), Some(b)) => constant(a.checked_rem(b).map_err(error)?),
+                        _ => atom(bi_op(render(&l), BiOpr::Modulo, render(&r))),
+                    },
+                }
+            },
+            NodeKind::Function(c) => {
+                let mut args = Vec::with_capacity(c.args.len());
+                for a in c.args.iter() {
+                    args.push(render(&self.sum(a)?));
+                }
+
+                atom(leaf(NodeKind::Function(Call {
+                    name: c.name.clone(),
+                    name_span: SPAN,
+                    bracket: c.bracket,
+                    args_span: SPAN,
+                    args,
+                })))
+            },
+        })
+    }
+
+    fn add(&mut self, mut a: Sum<N>, b: Sum<N>) -> Sum<N> {
+        for t in b {
+            // `∞ - ∞` is not zero, so infinite terms are never combined
+            let like = |u: &Term<N>| same_factors(&u.factors, &t.factors) && finite_term(u) && finite_term(&t);
+
+            match a.iter().position(like) {
+                Some(i) => {
+                    a[i].coefficient = a[i].coefficient.clone() + t.coefficient;
+
+                    if a[i].coefficient.is_zero() {
+                        let u = a.remove(i);
+                        self.dropped(&u);
+                    }
+                },
+                None if !t.coefficient.is_zero() => a.push(t),
+                None => {},
+            }
+        }
+
+        a
+    }
+
+    fn mul(&mut self, a: Sum<N>, b: Sum<N>) -> Result<Sum<N>, &'static str> {
+        if a.is_empty() || b.is_empty() {
+            // `0∞` is not zero
+            if !a.iter().chain(&b).all(finite_term) {
+                return Ok(atom(bi_op(render(&a), BiOpr::ImplicitMultiply, render(&b))));
+            }
+
+            for t in a.iter().chain(&b) {
+                self.dropped(t);
+            }
+
+            return Ok(Vec::new());
+        }
+
+        // a product with a sum is expanded unless the sum is a factor of the other side
+        let expand = |s: &Sum<N>, t: &Sum<N>| {
+            t.len() == 1 && {
+                let s = render(s);
+                t[0].factors.iter().all(|f| f.base != s)
+            }
+        };
+
+        if a.len() > 1 && b.len() > 1 || a.len() > 1 && !expand(&a, &b) || b.len() > 1 && !expand(&b, &a) {
+            return Ok(vec![self.mul_term(as_term(a), as_term(b))?]);
+        }
+
+        let mut out = Vec::new();
+        for t in &a {
+            for u in &b {
+                let p = self.mul_term(t.clone(), u.clone())?;
+                out = self.add(out, vec![p]);
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn mul_term(&mut self, mut t: Term<N>, u: Term<N>) -> Result<Term<N>, &'static str> {
+        t.coefficient = t.coefficient * u.coefficient;
+
+        for f in u.factors {
+            // `∞/∞` is not one
+            match t.factors.iter().position(|g| g.base == f.base && finite_node(&f.base)) {
+                Some(i) => {
+                    // a factor of a denominator cancelled
+                    if negative(&t.factors[i].exponent) != negative(&f.exponent) {
+                        self.condition(f.base);
+                    }
+
+                    t.factors[i].exponent = t.factors[i].exponent.clone() + f.exponent;
+                },
+                None => t.factors.push(f),
+            }
+        }
+
+        normalize(t)
+    }
+
+    fn inverse(&mut self, s: Sum<N>) -> Result<Sum<N>, &'static str> {
+        if s.is_empty() {
+            return Err("division by zero");
+        }
+
+        let t = as_term(s);
+        if !finite(&t.coefficient) {
+            return Ok(atom(bi_op(leaf(NodeKind::Number(N::one())), BiOpr::Divide, product(t, false))));
+        }
+
+        Ok(vec![Term {
+            coefficient: N::one() / t.coefficient,
+            factors: t
+                .factors
+                .into_iter()
+                .map(|f| Factor {
+                    base: f.base,
+                    exponent: -f.exponent,
+                })
+                .collect(),
+        }])
+    }
+
+    fn pow(&mut self, base: Sum<N>, exp: Sum<N>) -> Result<Sum<N>, &'static str> {
+        let Some(e) = as_constant(&exp) else {
+            return Ok(atom(bi_op(render(&base), BiOpr::Power, render(&exp))));
+        };
+
+        if e.is_zero() {
+            for t in &base {
+                self.dropped(t);
+            }
+
+            return Ok(constant(N::one()));
+        } else if is_one(&e) {
+            return Ok(base);
+        } else if base.is_empty() {
+            return Ok(constant(N::zero().checked_pow(e)?));
+        }
+
+        // `(x^a)^b` is not `x^(ab)` for every `b`, e.g. `(x^2)^½` is `|x|`
+        if !is_integer(&e) {
+            return Ok(vec![Term {
+                coefficient: N::one(),
+                factors: vec![Factor {
+                    base: render(&base),
+                    exponent: e,
+                }],
+            }]);
+        }
+
+        let t = as_term(base);
+        let mut factors = Vec::with_capacity(t.factors.len());
+
+        for f in t.factors {
+            let exponent = f.exponent.clone() * e.clone();
+
+            if negative(&f.exponent) && !negative(&exponent) {
+                self.condition(f.base.clone());
+            }
+
+            factors.push(Factor { base: f.base, exponent });
+        }
+
+        let t = normalize(Term {
+            coefficient: t.coefficient.checked_pow(e)?,
+            factors,
+        })?;
+        Ok(self.add(Vec::new(), vec![t]))
+    }
+
+    /// Records the denominators of a term that is gone
+    fn dropped(&mut self, t: &Term<N>) {
+        for f in &t.factors {
+            if negative(&f.exponent) {
+                self.condition(f.base.clone());
+            }
+        }
+    }
+
+    fn condition(&mut self, base: Node<N>) {
+        if !self.conditions.contains(&base) {
+            self.conditions.push(base);
+        }
+    }
+}
+
+/// Removes factors with a zero exponent and moves numbers with integer exponents into the
+/// coefficient, e.g. `2^½ 2^½` is `2`
+fn normalize<N: ComputableNumeral + NumberEq + NumberForm>(mut t: Term<N>) -> Result<Term<N>, &'static str> {
+    let mut i = 0;
+
+    while i < t.factors.len() {
+        let f = &t.factors[i];
+
+        match &f.base.kind {
+            _ if f.exponent.is_zero() => {},
+            NodeKind::Number(n) if is_integer(&f.exponent) => {
+                t.coefficient = t.coefficient * n.clone().checked_pow(f.exponent.clone())?;
+            },
+            _ => {
+                i += 1;
+                continue;
+            },
+        }
+
+        t.factors.remove(i);
+    }
+
+    Ok(t)
+}
+
+fn constant<N: ComputableNumeral>(n: N) -> Sum<N> {
+    if n.is_zero() {
+        Vec::new()
+    } else {
+        vec![Term {
+            coefficient: n,
+            factors: Vec::new(),
+        }]
+    }
+}
+
+fn atom<N: ComputableNumeral>(base: Node<N>) -> Sum<N> {
+    vec![Term {
+        coefficient: N::one(),
+        factors: vec![Factor {
+            base,
+            exponent: N::one(),
+        }],
+    }]
+}
+
+fn negate<N: ComputableNumeral>(mut s: Sum<N>) -> Sum<N> {
+    for t in &mut s {
+        t.coefficient = -t.coefficient.clone();
+    }
+
+    s
+}
+
+fn as_constant<N: ComputableNumeral>(s: &Sum<N>) -> Option<N> {
+    match s.as_slice() {
+        [] => Some(N::zero()),
+        [t] if t.factors.is_empty() => Some(t.coefficient.clone()),
+        _ => None,
+    }
+}
+
+/// A sum with several terms becomes a single factor
+fn as_term<N: ComputableNumeral + NumberEq + NumberForm>(mut s: Sum<N>) -> Term<N> {
+    if s.len() == 1 {
+        return s.remove(0);
+    }
+
+    Term {
+        coefficient: N::one(),
+        factors: vec![Factor {
+            base: render(&s),
+            exponent: N::one(),
+        }],
+    }
+}
+
+fn same_factors<N: ComputableNumeral + NumberEq + NumberForm>(a: &[Factor<N>], b: &[Factor<N>]) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|f| b.iter().any(|g| f.base == g.base && f.exponent.number_eq(&g.exponent)))
+}
+
+fn is_one<N: ComputableNumeral + NumberEq + NumberForm>(n: &N) -> bool { n.number_eq(&N::one()) }
+
+/// Whether `n` is neither infinite nor NaN
+fn finite<N: ComputableNumeral>(n: &N) -> bool { (n.clone() - n.clone()).is_zero() }
+
+/// Whether a tree has neither `∞` nor a number that is not [`finite`]
+fn finite_node<N: ComputableNumeral>(n: &Node<N>) -> bool {
+    match &n.kind {
+        NodeKind::Number(n) => finite(n),
+        NodeKind::Constant(c) => c != "∞",
+        NodeKind::Group(_, v) | NodeKind::UnOp(_, v) => finite_node(v),
+        NodeKind::BiOp(l, _, r) => finite_node(l) && finite_node(r),
+        NodeKind::Function(c) => c.args.iter().all(finite_node),
+    }
+}
+
+fn finite_term<N: ComputableNumeral>(t: &Term<N>) -> bool {
+    finite(&t.coefficient) && t.factors.iter().all(|f| finite_node(&f.base))
+}
+
+/// Whether `n` is a finite integer, numbers without `trunc` are not
+fn is_integer<N: ComputableNumeral + NumberEq + NumberForm>(n: &N) -> bool {
+    finite(n) && N::execute("trunc", slice::from_ref(n)).is_ok_and(|t| t.number_eq(n))
+}
+
+/// Whether `n` is a negative real number, numbers without `abs` are not unless their
+/// [`NumberForm`] has a sign, like `-∞`
+fn negative<N: ComputableNumeral + NumberEq + NumberForm>(n: &N) -> bool {
+    n.is_negative() || !n.is_zero() && N::execute("abs", slice::from_ref(n)).is_ok_and(|a| a.number_eq(&-n.clone()))
+}
+
+/// Orders integers before other numbers, which are all equal
+fn cmp_number<N: ComputableNumeral + NumberEq + NumberForm>(a: &N, b: &N) -> Ordering {
+    match (is_integer(a), is_integer(b)) {
+        (true, true) => {
+            let d = a.clone() - b.clone();
+
+            if d.is_zero() {
+                Ordering::Equal
+            } else if negative(&d) {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        },
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => Ordering::Equal,
+    }
+}
+
+/// Orders numbers before constants, variables, functions and other expressions
+fn cmp_node<N: ComputableNumeral + NumberEq + NumberForm>(a: &Node<N>, b: &Node<N>) -> Ordering {
+    let rank = |n: &Node<N>| match &n.kind {
+        NodeKind::Number(_) => 0,
+        NodeKind::Constant(c) if N::from_constant(c).is_some() => 1,
+        NodeKind::Constant(_) => 2,
+        NodeKind::Function(_) => 3,
+        NodeKind::UnOp(..) => 4,
+        NodeKind::BiOp(..) => 5,
+        NodeKind::Group(..) => 6,
+    };
+    let nodes = |a: &[Node<N>], b: &[Node<N>]| {
+        a.iter().zip(b).map(|(a, b)| cmp_node(a, b)).find(|o| o.is_ne()).unwrap_or(a.len().cmp(&b.len()))
+    };
+
+    rank(a).cmp(&rank(b)).then_with(|| match (&a.kind, &b.kind) {
+        (NodeKind::Number(a), NodeKind::Number(b)) => cmp_number(a, b),
+        (NodeKind::Constant(a), NodeKind::Constant(b)) => a.cmp(b),
+        (NodeKind::Function(a), NodeKind::Function(b)) => a.name.cmp(&b.name).then_with(|| nodes(&a.args, &b.args)),
+        (NodeKind::UnOp(p, a), NodeKind::UnOp(q, b)) => (*p as u8).cmp(&(*q as u8)).then_with(|| cmp_node(a, b)),
+        (NodeKind::BiOp(a, p, c), NodeKind::BiOp(b, q, d)) => {
+            (*p as u8).cmp(&(*q as u8)).then_with(|| cmp_node(a, b)).then_with(|| cmp_node(c, d))
+        },
+        (NodeKind::Group(_, a), NodeKind::Group(_, b)) => cmp_node(a, b),
+        _ => Ordering::Equal,
+    })
+}
+
+/// Orders factors by their bases, higher powers first
+fn cmp_factor<N: ComputableNumeral + NumberEq + NumberForm>(a: &Factor<N>, b: &Factor<N>) -> Ordering {
+    cmp_node(&a.base, &b.base).then_with(|| cmp_number(&b.exponent, &a.exponent))
+}
+
+/// Orders terms by their sorted factors, so that `x^2` comes before `x` and numbers come last
+fn cmp_term<N: ComputableNumeral + NumberEq + NumberForm>(a: &Term<N>, b: &Term<N>) -> Ordering {
+    a.factors
+        .iter()
+        .zip(&b.factors)
+        .map(|(f, g)| cmp_factor(f, g))
+        .find(|o| o.is_ne())
+        .unwrap_or(b.factors.len().cmp(&a.factors.len()))
+}
+
+fn leaf<N>(kind: NodeKind<N>) -> Node<N> { Node { kind, span: SPAN } }
+
+fn bi_op<N>(l: Node<N>, op: BiOpr, r: Node<N>) -> Node<N> { leaf(NodeKind::BiOp(Box::new(l), op, Box::new(r))) }
+
+fn render<N: ComputableNumeral + NumberEq + NumberForm>(s: &Sum<N>) -> Node<N> {
+    let mut terms = s.clone();
+    terms.sort_by(cmp_term);
+
+    let mut out = None;
+    for mut t in terms {
+        let neg = negative(&t.coefficient);
+        if neg {
+            t.coefficient = -t.coefficient;
+        }
+
+        out = Some(match out {
+            None => product(t, neg),
+            Some(l) => bi_op(l, if neg { BiOpr::Subtract } else { BiOpr::Add }, product(t, false)),
+        });
+    }
+
+    out.unwrap_or_else(|| leaf(NodeKind::Number(N::zero())))
+}
+
+/// Writes a term with a positive coefficient, denominators are written as a fraction. `minus`
+/// negates the first factor, so that `-3x` is not written as `-(3x)`.
+fn product<N: ComputableNumeral + NumberEq + NumberForm>(mut t: Term<N>, minus: bool) -> Node<N> {
+    t.factors.sort_by(cmp_factor);
+
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+
+    if !is_one(&t.coefficient) {
+        let inverse = N::one() / t.coefficient.clone();
+
+        if !is_integer(&t.coefficient) && !inverse.is_zero() && is_integer(&inverse) {
+            denominator.push(leaf(NodeKind::Number(inverse)));
+        } else {
+            numerator.push(leaf(NodeKind::Number(t.coefficient)));
+        }
+    }
+
+    for f in t.factors {
+        let (list, exponent) = if negative(&f.exponent) {
+            (&mut denominator, -f.exponent)
+        } else {
+            (&mut numerator, f.exponent)
+        };
+
+        list.push(if is_one(&exponent) {
+            f.base
+        } else {
+            bi_op(f.base, BiOpr::Power, leaf(NodeKind::Number(exponent)))
+        });
+    }
+
+    let join = |v: Vec<Node<N>>| v.into_iter().reduce(|l, r| bi_op(l, BiOpr::ImplicitMultiply, r));
+    if numerator.is_empty() {
+        numerator.push(leaf(NodeKind::Number(N::one())));
+    }
+
+    if minus {
+        numerator[0] = leaf(NodeKind::UnOp(UnOpr::Minus, Box::new(numerator[0].clone())));
+    }
+
+    let numerator = join(numerator).unwrap();
+
+    match join(denominator) {
+        Some(d) => bi_op(numerator, BiOpr::Divide, d),
+        None => numerator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::*;
+    use alloc::string::{String, ToString};
+
+    fn simplified(n: Node<f64>) -> String { n.simplify().unwrap().node.to_string() }
+
+    #[test]
+    fn simplify() {
+        let x = || var::<f64>("x");
+        let y = || var::<f64>("y");
+
+        for (n, expected) in [
+            (num(2) * x() + num(3) * x(), "5x"),
+            (to_nodes("2π + 3π - ½π").unwrap(), "4.5π"),
+            (num(1) + x() + x().pow(num(2)) + num(2) * x(), "x^2 + 3x + 1"),
+            (y() * x() - x() * y() + y(), "y"),
+            (num(2) * (x() + num(1)) - num(2), "2x"),
+            (x().pow(num(2)) * x().pow(num(3)) / x(), "x^4"),
+            (x().pow(num(2)).pow(num(3)), "x^6"),
+            ((num(2) * x() * y()).pow(num(2)), "4x^2y^2"),
+            (x().pow(num(2)).pow(num(0.5)), "(x^2)^0.5"),
+            (x() / num(2) - y() / x(), "x / 2 - y / x"),
+            (-x() * num(3) + func("sin", [x() + x()]), "-3x + sin(2x)"),
+            ((x() + num(1)) * (x() + num(1)) / (x() + num(1)).pow(num(3)), "1 / (x + 1)"),
+            (to_nodes("2^0.5 * 2^0.5 + 5 % 3").unwrap(), "4"),
+            (x() - x(), "0"),
+            (to_nodes_with_variables("2x + 3x").unwrap(), "5x"),
+            (to_nodes("∞ - ∞").unwrap(), "∞ - ∞"),
+            (to_nodes("0∞").unwrap(), "0∞"),
+            (to_nodes("∞/∞").unwrap(), "∞ / ∞"),
+            (x() * var("∞") - x() * var("∞"), "∞x - ∞x"),
+            (num(f64::INFINITY) * x() - num(f64::INFINITY) * x(), "inf x - inf x"),
+        ] {
+            assert_eq!(simplified(n.clone()), expected, "{n}");
+        }
+
+        let s = (x().pow(num(2)) / num(2) - num(3) / x() * y()).simplify().unwrap().node;
+        assert_eq!(latex::LatexDisplay { node: &s, src: "" }.to_string(), r"\frac{x^{2}}{2} - \frac{3y}{x}");
+    }
+
+    #[test]
+    fn conditions() {
+        let x = || var::<f64>("x");
+
+        let s = (num(2) * x() / x()).simplify().unwrap();
+        assert_eq!(s.node, num(2));
+        assert_eq!(s.conditions, [x()]);
+
+        let s = ((x() + num(1)) / (x() + num(1)) + num(0) / x()).simplify().unwrap();
+        assert_eq!(s.node, num(1));
+        assert_eq!(s.conditions, [x() + num(1), x()]);
+
+        let s = (num(1) / x()).pow(num(0)).simplify().unwrap();
+        assert_eq!(s.node, num(1));
+        assert_eq!(s.conditions, [x()]);
+
+        assert!(num::<f64>(1).simplify().unwrap().conditions.is_empty());
+        assert_eq!((x() / (x() - x())).simplify().unwrap_err().message, "division by zero");
+    }
+
+    #[cfg(feature = "num_rational")]
+    #[test]
+    fn exact() {
+        use num_bigint::BigInt;
+        use rational::Rational;
+
+        let x = || var::<Rational<BigInt>>("x");
+        let n = to_nodes("0.1 + 0.2").unwrap() * x() + x() / num(Rational(BigInt::from(3).into()));
+        let s = n.simplify().unwrap().node;
+        assert_eq!(latex::LatexDisplay { node: &s, src: "" }.to_string(), r"\frac{19}{30}x");
+    }
+}
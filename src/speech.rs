@@ -0,0 +1,297 @@
+use core::fmt;
+use alloc::{format, string::String, vec::Vec};
+use crate::*;
+use crate::latex::{needs_brackets, number, unwrap, Side};
+
+/// Spoken English in the style of MathSpeak, e.g. "the fraction with numerator 1 plus π and
+/// denominator 2"
+pub struct SpokenDisplay<'a, T> {
+    pub node: &'a Node<T>,
+    /// The same as [`LatexDisplay::src`](crate::latex::LatexDisplay::src)
+    pub src: &'a str,
+}
+
+struct Word(String, Kind);
+
+#[derive(PartialEq)]
+enum Kind {
+    Say,
+    /// Marks the end of a fraction, root or exponent
+    End,
+    /// Closes a bracket
+    Close,
+}
+
+impl<T: fmt::Display + NumberForm> fmt::Display for SpokenDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = Vec::new();
+        self.speak(self.node, 0, &mut words);
+
+        let mut first = true;
+        for (i, w) in words.iter().enumerate() {
+            // ends are only needed if something other than an end or a closing bracket follows
+            if w.1 == Kind::End && words[i..].iter().find(|w| w.1 != Kind::End).is_none_or(|w| w.1 == Kind::Close) {
+                continue;
+            }
+
+            if !first {
+                f.write_str(" ")?;
+            }
+
+            f.write_str(&w.0)?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: fmt::Display + NumberForm> SpokenDisplay<'_, T> {
+    /// `depth` is the number of fractions and roots that `node` is in
+    fn speak(&self, node: &Node<T>, depth: usize, w: &mut Vec<Word>) {
+        match &node.kind {
+            NodeKind::Number(n) => {
+                let n = number(n, &node.span, self.src);
+
+                match n.strip_prefix('-') {
+                    Some(n) => say(w, &format!("negative {n}")),
+                    None => say(w, &n),
+                }
+            },
+            NodeKind::Constant(c) => say(w, constant(c)),
+            NodeKind::Group(_, v) => self.speak(v, depth, w),
+            NodeKind::UnOp(op, v) => {
+                say(w, match op {
+                    UnOpr::Plus => "plus",
+                    UnOpr::Minus => "negative",
+                });
+                self.operand(v, needs_brackets(node, Side::Right), depth, w);
+            },
+            NodeKind::BiOp(l, BiOpr::Divide, r) => {
+                let inner = "inner ".repeat(depth);
+
+                say(w, &format!("the {inner}fraction with numerator"));
+                self.speak(l, depth + 1, w);
+                say(w, "and denominator");
+                self.speak(r, depth + 1, w);
+                end(w, &format!("end {inner}fraction"));
+            },
+            NodeKind::BiOp(l, BiOpr::Power, r) => {
+                self.operand(l, needs_brackets(node, Side::Left), depth, w);
+
+                match &unwrap(r).kind {
+                    NodeKind::Number(n) if number(n, &unwrap(r).span, self.src) == "2" => say(w, "squared"),
+                    NodeKind::Number(n) if number(n, &unwrap(r).span, self.src) == "3" => say(w, "cubed"),
+                    _ => {
+                        say(w, "to the power of");
+                        self.speak(r, depth, w);
+                        end(w, "end exponent");
+                    },
+                }
+            },
+            NodeKind::BiOp(l, op, r) => {
+                self.operand(l, needs_brackets(node, Side::Left), depth, w);
+
+                match op {
+                    BiOpr::Add => say(w, "plus"),
+                    BiOpr::Subtract => say(w, "minus"),
+                    BiOpr::Multiply => say(w, "times"),
+                    // `2π` is "2 pi" but `2 3` is "2 times 3"
+                    BiOpr::ImplicitMultiply if matches!(unwrap(r).kind, NodeKind::Number(_)) => say(w, "times"),
+                    BiOpr::ImplicitMultiply => {},
+                    _ => say(w, "modulo"),
+                }
+
+                self.operand(r, needs_brackets(node, Side::Right), depth, w);
+            },
+            NodeKind::Function(c) => self.function(c, depth, w),
+        }
+    }
+
+    fn function(&self, c: &Call<T>, depth: usize, w: &mut Vec<Word>) {
+        let a = &c.args;
+        let inner = "inner ".repeat(depth);
+
+        match (c.name.as_str(), a.len()) {
+            ("sqrt" | "√", 1) => {
+                say(w, &format!("the {inner}square root of"));
+                self.speak(&a[0], depth + 1, w);
+                end(w, &format!("end {inner}root"));
+            },
+            ("cbrt" | "∛", 1) => {
+                say(w, &format!("the {inner}cube root of"));
+                self.speak(&a[0], depth + 1, w);
+                end(w, &format!("end {inner}root"));
+            },
+            ("log", 2) => {
+                say(w, "the log base");
+                self.operand(&a[1], !single(&a[1]), depth, w);
+                say(w, "of");
+                self.argument("log", &a[0], depth, w);
+            },
+            (name, 1) => {
+                say(w, &format!("the {} of", function(name)));
+                self.argument(name, &a[0], depth, w);
+            },
+            (name, _) => {
+                say(w, &format!("the {} of open paren", function(name)));
+
+                for (i, r) in a.iter().enumerate() {
+                    if i != 0 {
+                        say(w, "comma");
+                    }
+
+                    self.speak(r, depth, w);
+                }
+
+                w.push(Word("close paren".into(), Kind::Close));
+            },
+        }
+    }
+
+    /// Speaks the last argument of a function, in brackets unless it is a single word, which is
+    /// ended instead
+    fn argument(&self, name: &str, node: &Node<T>, depth: usize, w: &mut Vec<Word>) {
+        if single(node) {
+            self.speak(node, depth, w);
+            end(w, &format!("end {}", function(name)));
+        } else {
+            self.operand(node, true, depth, w);
+        }
+    }
+
+    fn operand(&self, node: &Node<T>, brackets: bool, depth: usize, w: &mut Vec<Word>) {
+        if !brackets {
+            return self.speak(node, depth, w);
+        }
+
+        let kind = match &node.kind {
+            NodeKind::Group(BKind::Square, _) => "bracket",
+            NodeKind::Group(BKind::Curly, _) => "brace",
+            _ => "paren",
+        };
+
+        say(w, &format!("open {kind}"));
+        self.speak(node, depth, w);
+        w.push(Word(format!("close {kind}"), Kind::Close));
+    }
+}
+
+fn say(w: &mut Vec<Word>, s: &str) { w.push(Word(s.into(), Kind::Say)); }
+
+fn end(w: &mut Vec<Word>, s: &str) { w.push(Word(s.into(), Kind::End)); }
+
+/// Whether `node` is spoken as a single word
+fn single<T>(node: &Node<T>) -> bool { matches!(unwrap(node).kind, NodeKind::Number(_) | NodeKind::Constant(_)) }
+
+fn function(name: &str) -> &str {
+    match name {
+        "abs" => "absolute value",
+        "floor" => "floor",
+        "ceil" => "ceiling",
+        "conj" => "complex conjugate",
+        "sin" => "sine",
+        "cos" => "cosine",
+        "tan" => "tangent",
+        "sinh" => "hyperbolic sine",
+        "cosh" => "hyperbolic cosine",
+        "tanh" => "hyperbolic tangent",
+        "arcsin" => "arc sine",
+        "arccos" => "arc cosine",
+        "arctan" => "arc tangent",
+        "ln" => "natural log",
+        "exp" => "exponential",
+        "max" => "maximum",
+        "min" => "minimum",
+        "trunc" => "truncation",
+        "fract" => "fractional part",
+        name => name,
+    }
+}
+
+fn constant(c: &str) -> &str {
+    match c {
+        "π" => "pi",
+        "φ" | "ϕ" => "phi",
+        "τ" => "tau",
+        "γ" => "gamma",
+        "∞" => "infinity",
+        "c_m/s" => "the speed of light",
+        "↉" => "zero thirds",
+        "½" => "one half",
+        "⅓" => "one third",
+        "¼" => "one quarter",
+        "⅕" => "one fifth",
+        "⅙" => "one sixth",
+        "⅐" => "one seventh",
+        "⅛" => "one eighth",
+        "⅑" => "one ninth",
+        "⅒" => "one tenth",
+        "⅔" => "two thirds",
+        "⅖" => "two fifths",
+        "¾" => "three quarters",
+        "⅗" => "three fifths",
+        "⅜" => "three eighths",
+        "⅘" => "four fifths",
+        "⅚" => "five sixths",
+        "⅝" => "five eighths",
+        "⅞" => "seven eighths",
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn spoken(s: &str) -> String {
+        let node = crate::Expr::parse(s).unwrap();
+        SpokenDisplay { node: &node, src: s }.to_string()
+    }
+
+    #[test]
+    fn snapshots() {
+        for (s, expected) in [
+            ("(e+1)/2", "the fraction with numerator e plus 1 and denominator 2"),
+            ("1/2 + 3", "the fraction with numerator 1 and denominator 2 end fraction plus 3"),
+            (
+                "(1/2)/3",
+                "the fraction with numerator the inner fraction with numerator 1 and denominator 2 end inner \
+                 fraction and denominator 3",
+            ),
+            ("π^2 + e^3 - 2^π", "pi squared plus e cubed minus 2 to the power of pi"),
+            ("2^(1+π)*3", "2 to the power of 1 plus pi end exponent times 3"),
+            ("sqrt(2)π", "the square root of 2 end root pi"),
+            ("sqrt(1/2)", "the square root of the inner fraction with numerator 1 and denominator 2"),
+            (
+                "sqrt(1/2)+1",
+                "the square root of the inner fraction with numerator 1 and denominator 2 end inner fraction end root \
+                 plus 1",
+            ),
+            ("-(1+2)", "negative open paren 1 plus 2 close paren"),
+            ("2[1-π]", "2 open bracket 1 minus pi close bracket"),
+            ("sin(π/2)", "the sine of open paren the fraction with numerator pi and denominator 2 close paren"),
+            ("log(8, 2) + abs(½)", "the log base 2 of 8 end log plus the absolute value of one half"),
+            ("2^π*3", "2 to the power of pi end exponent times 3"),
+            ("sin(2)π", "the sine of 2 end sine pi"),
+            ("sin(2)^2", "the sine of 2 end sine squared"),
+            ("max(1, 2 3)", "the maximum of open paren 1 comma 2 times 3 close paren"),
+        ] {
+            assert_eq!(spoken(s), expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn built() {
+        use crate::build::*;
+
+        for (n, expected) in [
+            (num::<f64>(-2.0).pow(num(2.0)), "open paren negative 2 close paren squared"),
+            (num(2.0) * num(-3.0), "2 times open paren negative 3 close paren"),
+            (-num(-1.5), "negative open paren negative 1.5 close paren"),
+        ] {
+            assert_eq!(SpokenDisplay { node: &n, src: "" }.to_string(), expected);
+        }
+    }
+}
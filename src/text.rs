@@ -0,0 +1,268 @@
+use core::fmt;
+use alloc::string::String;
+use crate::*;
+
+/// Plain text with only the brackets that are needed to parse it back into the same tree. Numbers
+/// are written with their `Display`, so an `f64` that is not finite is written as `inf` or `NaN`
+/// and can not be parsed back.
+pub struct TextDisplay<'a, T> {
+    pub node: &'a Node<T>,
+    /// Use `×` and `√` instead of `*` and `sqrt`
+    pub unicode: bool,
+}
+
+impl<T: fmt::Display> fmt::Display for TextDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = String::new();
+        self.write(self.node, &mut s);
+        f.write_str(&s)
+    }
+}
+
+impl<T: fmt::Display> TextDisplay<'_, T> {
+    fn write(&self, node: &Node<T>, s: &mut String) {
+        use fmt::Write;
+
+        match &node.kind {
+            NodeKind::Number(n) => {
+                let start = s.len();
+                let _ = write!(s, "{n}");
+
+                if s[start..].starts_with('-') {
+                    s.insert(start, '(');
+                    s.push(')');
+                }
+            },
+            NodeKind::Constant(c) => s.push_str(c),
+            NodeKind::Group(_, v) => self.write(v, s),
+            NodeKind::UnOp(op, v) => {
+                s.push(match op {
+                    UnOpr::Plus => '+',
+                    UnOpr::Minus => '-',
+                });
+                self.operand(v, binary(v).is_some(), s);
+            },
+            NodeKind::BiOp(l, op, r) => {
+                let p = op.percedence();
+                let lp = binary(l).is_some_and(|l| l.percedence() < p || (l.percedence() == p && !op.is_left_associative()));
+                let rp = binary(r).is_some_and(|r| r.percedence() < p || (r.percedence() == p && op.is_left_associative()));
+
+                self.operand(l, lp, s);
+
+                match op {
+                    BiOpr::ImplicitMultiply => {
+                        // implicit multiplications only continue with numbers, constants, functions or brackets
+                        let rp = rp || starts_with_unary(r);
+                        let mut right = String::new();
+                        self.operand(r, rp, &mut right);
+
+                        // letters too, a run of them is one name to `to_nodes_with_variables`
+                        if s.ends_with(is_numeric) && right.starts_with(is_numeric)
+                            || s.ends_with(char::is_alphabetic) && right.starts_with(char::is_alphabetic)
+                        {
+                            s.push(' ');
+                        }
+
+                        s.push_str(&right);
+                        return;
+                    },
+                    BiOpr::Power => s.push('^'),
+                    op => {
+                        s.push_str(match op {
+                            BiOpr::Add => " + ",
+                            BiOpr::Subtract => " - ",
+                            BiOpr::Multiply if self.unicode => " × ",
+                            BiOpr::Multiply => " * ",
+                            BiOpr::Divide => " / ",
+                            _ => " % ",
+                        });
+                    },
+                }
+
+                self.operand(r, rp, s);
+            },
+            NodeKind::Function(c) => {
+                s.push_str(match (c.name.as_str(), self.unicode) {
+                    ("sqrt" | "√", true) => "√",
+                    ("sqrt" | "√", false) => "sqrt",
+                    ("cbrt" | "∛", true) => "∛",
+                    ("cbrt" | "∛", false) => "cbrt",
+                    (name, _) => name,
+                });
+                s.push('(');
+
+                for (i, a) in c.args.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str(", ");
+                    }
+
+                    self.write(a, s);
+                }
+
+                s.push(')');
+            },
+        }
+    }
+
+    fn operand(&self, node: &Node<T>, brackets: bool, s: &mut String) {
+        if brackets {
+            s.push('(');
+            self.write(node, s);
+            s.push(')');
+        } else {
+            self.write(node, s);
+        }
+    }
+}
+
+/// The operator of a binary operation, looking through brackets
+fn binary<T>(node: &Node<T>) -> Option<BiOpr> {
+    match &node.kind {
+        NodeKind::BiOp(_, op, _) => Some(*op),
+        NodeKind::Group(_, v) => binary(v),
+        _ => None,
+    }
+}
+
+/// Whether the text of `node` starts with a unary operator
+fn starts_with_unary<T>(node: &Node<T>) -> bool {
+    match &node.kind {
+        NodeKind::UnOp(..) => true,
+        NodeKind::BiOp(l, op, _) => {
+            let lp = binary(l).is_some_and(|l| l.percedence() < op.percedence());
+            !lp && starts_with_unary(l)
+        },
+        NodeKind::Group(_, v) => starts_with_unary(v),
+        _ => false,
+    }
+}
+
+fn is_numeric(c: char) -> bool { c.is_ascii_digit() || c == '.' }
+
+impl<T: fmt::Display> fmt::Display for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        TextDisplay {
+            node: self,
+            unicode: false,
+        }
+        .fmt(f)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.0.fmt(f) }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// Drops brackets, so that a tree can be compared with the one that was printed without the
+    /// redundant ones
+    struct Ungrouped;
+
+    impl visit::Fold<f64> for Ungrouped {
+        fn fold_call(&mut self, c: Call<f64>, span: Span) -> Node<f64> {
+            let bracket = BKind::Round;
+            Node {
+                kind: NodeKind::Function(Call { bracket, ..c }),
+                span,
+            }
+        }
+
+        fn fold_group(&mut self, _k: BKind, v: Node<f64>, _span: Span) -> Node<f64> { v }
+    }
+
+    /// Prints `s` and parses it back. Printing only drops redundant brackets, so the parsed tree
+    /// prints the same again and is the tree of `s` without them.
+    fn round_trip(s: &str) -> String {
+        use visit::Fold;
+
+        let n = to_nodes::<f64>(s).unwrap();
+        let printed = n.to_string();
+        let parsed = to_nodes::<f64>(&printed).unwrap();
+        assert_eq!(to_nodes::<f64>(&parsed.to_string()).unwrap(), parsed, "{printed:?}");
+        assert_eq!(Ungrouped.fold_node(parsed), Ungrouped.fold_node(n), "{s:?} was printed as {printed:?}");
+
+        printed
+    }
+
+    #[test]
+    fn minimal_brackets() {
+        for (s, expected) in [
+            ("((1+2))*3", "(1 + 2) * 3"),
+            ("1-(2-3)", "1 - (2 - 3)"),
+            ("(1-2)-3", "1 - 2 - 3"),
+            ("2^(3^2)", "2^3^2"),
+            ("(2^3)^2", "(2^3)^2"),
+            ("-2^2", "-2^2"),
+            ("-(2^2)", "-(2^2)"),
+            ("2*(-3)", "2 * -3"),
+            ("2(-3)", "2(-3)"),
+            ("2(3+4)", "2(3 + 4)"),
+            ("2 3", "2 3"),
+            ("2π[1]", "2π1"),
+            ("2(-3^2)", "2(-3^2)"),
+            ("1/(2*3)%4", "1 / (2 * 3) % 4"),
+            ("max[1, (2+3)]", "max(1, 2 + 3)"),
+        ] {
+            assert_eq!(round_trip(s), expected);
+        }
+
+        // without redundant brackets the printed text is parsed into the very same tree
+        for s in ["(1 + 2) * 3", "1 - (2 - 3)", "(2^3)^2", "-(2^2)", "2 * -3", "2(-3)", "2(-3^2)", "max(1, 2 + 3)"] {
+            let n = to_nodes::<f64>(s).unwrap();
+            assert_eq!(to_nodes::<f64>(&n.to_string()).unwrap(), n, "{s}");
+        }
+    }
+
+    #[test]
+    fn not_finite() {
+        // `f64` prints these as `inf`, `-inf` and `NaN`, which are not input
+        for n in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            let s = build::num::<f64>(n).to_string();
+            assert!(to_nodes::<f64>(&s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn unicode() {
+        let n = to_nodes::<f64>("sqrt(4)*∛(8)").unwrap();
+        let s = TextDisplay { node: &n, unicode: true }.to_string();
+        assert_eq!(s, "√(4) × ∛(8)");
+        assert_eq!(to_nodes::<f64>(&s).unwrap().to_string(), "sqrt(4) * cbrt(8)");
+    }
+
+    #[test]
+    fn random_round_trips() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        const FRAGMENTS: &[&str] = &[
+            "1", "2.5", "10", "π", "e", "+", "-", "*", "/", "%", "^", "(", ")", "[", "]", "max(", ",", " ",
+        ];
+
+        let mut parsed = 0;
+        for _ in 0..2000 {
+            let len = next() % 12 + 1;
+            let s: String = (0..len).map(|_| FRAGMENTS[next() % FRAGMENTS.len()]).collect();
+
+            if to_nodes::<f64>(&s).is_ok() {
+                round_trip(&s);
+                parsed += 1;
+            }
+        }
+
+        assert!(parsed > 100);
+    }
+}
@@ -7,7 +7,7 @@ pub trait ComputableNumeral:
     + ExecuteFunction
     + num_traits::Num
     + core::ops::Neg<Output = Self>
-    + num_traits::Pow<Self, Output = Self>
+    + CheckedOperation
 {
 }
 
@@ -18,15 +18,138 @@ impl<
             + ExecuteFunction
             + num_traits::Num
             + core::ops::Neg<Output = T>
-            + num_traits::Pow<T, Output = T>,
+            + CheckedOperation,
     > ComputableNumeral for T
 {
 }
 
+/// Operations that are not defined for every input. Instead of panicking, implementors should
+/// report those inputs as an error.
+pub trait CheckedOperation
+where
+    Self: Sized,
+{
+    fn checked_rem(self, rhs: Self) -> Result<Self, &'static str>;
+    fn checked_pow(self, exp: Self) -> Result<Self, &'static str>;
+}
+
+macro_rules! infallible_checked_op {
+    ($($type: ty: $pow: expr),*) => {
+        $(
+            #[cfg(feature = "any_num")]
+            impl CheckedOperation for $type {
+                fn checked_rem(self, rhs: Self) -> Result<Self, &'static str> { Ok(self % rhs) }
+
+                fn checked_pow(self, exp: Self) -> Result<Self, &'static str> {
+                    Ok(($pow)(self, exp))
+                }
+            }
+        )*
+    };
+}
+
+infallible_checked_op!(f32: float!(f32, powf), f64: float!(f64, powf));
+#[cfg(feature = "num_complex")]
+infallible_checked_op!(
+    num_complex::Complex<f32>: num_complex::Complex::<f32>::powc,
+    num_complex::Complex<f64>: num_complex::Complex::<f64>::powc
+);
+
+/// How numbers are compared and hashed when trees are. Floats are compared by their bits, so `NaN`
+/// equals itself and `0.0` does not equal `-0.0`.
+pub trait NumberEq {
+    fn number_eq(&self, other: &Self) -> bool;
+    fn number_hash<H: core::hash::Hasher>(&self, state: &mut H);
+}
+
+macro_rules! number_eq_bits {
+    ($($t: ty),*) => {$(
+        impl NumberEq for $t {
+            fn number_eq(&self, other: &Self) -> bool { self.to_bits() == other.to_bits() }
+
+            fn number_hash<H: core::hash::Hasher>(&self, state: &mut H) { core::hash::Hash::hash(&self.to_bits(), state) }
+        }
+    )*};
+}
+
+macro_rules! number_eq_derived {
+    ($($t: ty),*) => {$(
+        impl NumberEq for $t {
+            fn number_eq(&self, other: &Self) -> bool { self == other }
+
+            fn number_hash<H: core::hash::Hasher>(&self, state: &mut H) { core::hash::Hash::hash(self, state) }
+        }
+    )*};
+}
+
+number_eq_bits!(f32, f64);
+number_eq_derived!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, crate::Literal);
+
+/// How a number is written, so that the renderers can bracket it like the operation it looks like.
+/// The defaults suit numbers that are written without a sign, e.g. `12` or `(1 / 2)`.
+pub trait NumberForm {
+    /// Whether the number is written with a leading minus, e.g. `-2` like `-x`
+    fn is_negative(&self) -> bool { false }
+
+    /// Whether the number is written as a sum, e.g. `1+2i` like `1 + x`
+    fn is_sum(&self) -> bool { false }
+}
+
+macro_rules! number_form {
+    (float $($t: ty),*) => {$(
+        impl NumberForm for $t {
+            fn is_negative(&self) -> bool { self.is_sign_negative() && !self.is_nan() }
+        }
+    )*};
+    (signed $($t: ty),*) => {$(
+        impl NumberForm for $t {
+            fn is_negative(&self) -> bool { *self < 0 }
+        }
+    )*};
+    (unsigned $($t: ty),*) => {$(
+        impl NumberForm for $t {}
+    )*};
+}
+
+number_form!(float f32, f64);
+number_form!(signed i8, i16, i32, i64, i128, isize);
+number_form!(unsigned u8, u16, u32, u64, u128, usize);
+
+impl NumberForm for crate::Literal {
+    fn is_negative(&self) -> bool { self.0.starts_with('-') }
+
+    fn is_sum(&self) -> bool { crate::latex::number_parts(self.0.trim()).0.is_some() }
+}
+
+/// Written as `re+imi` or `re-imi`
+#[cfg(feature = "num_complex")]
+impl<T> NumberForm for num_complex::Complex<T> {
+    fn is_sum(&self) -> bool { true }
+}
+
+#[cfg(feature = "num_complex")]
+impl<T: NumberEq> NumberEq for num_complex::Complex<T> {
+    fn number_eq(&self, other: &Self) -> bool { self.re.number_eq(&other.re) && self.im.number_eq(&other.im) }
+
+    fn number_hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.re.number_hash(state);
+        self.im.number_hash(state);
+    }
+}
+
 pub trait Numeral: FromStr + FromConstant {}
 
 impl<T: FromStr + FromConstant> Numeral for T {}
 
+/// Names of every constant that the parser recognizes. Whether a constant is supported depends on
+/// the [`FromConstant`] implementation of the number type.
+pub const CONSTANTS: &[&str] = &[
+    "π", "φ", "ϕ", "e", "τ", "γ", "c_m/s", "i", "∞", "↉", "½", "⅓", "¼", "⅕", "⅙", "⅐", "⅛", "⅑", "⅒",
+    "⅔", "⅖", "¾", "⅗", "⅜", "⅘", "⅚", "⅝", "⅞",
+];
+
+pub fn is_constant(c: &str) -> bool { CONSTANTS.contains(&c) }
+
 pub trait FromConstant
 where
     Self: Sized,
@@ -38,11 +161,12 @@ impl FromConstant for f32 {
     fn from_constant(c: &str) -> Option<Self> {
         match c {
             "π" => Some(core::f32::consts::PI),
-            "φ" | "ϕ" => Some(1.61803398874989484820),
+            "φ" | "ϕ" => Some(1.618_034),
             "e" => Some(core::f32::consts::E),
             "τ" => Some(core::f32::consts::TAU),
-            "γ" => Some(0.57721566490153286060),
+            "γ" => Some(0.577_215_7),
             "c_m/s" => Some(299792458.0),
+            "∞" => Some(f32::INFINITY),
 
             "↉" => Some(0.0 / 3.0),
 
@@ -78,11 +202,12 @@ impl FromConstant for f64 {
     fn from_constant(c: &str) -> Option<Self> {
         match c {
             "π" => Some(core::f64::consts::PI),
-            "φ" | "ϕ" => Some(1.61803398874989484820),
+            "φ" | "ϕ" => Some(1.618_033_988_749_895),
             "e" => Some(core::f64::consts::E),
             "τ" => Some(core::f64::consts::TAU),
-            "γ" => Some(0.57721566490153286060),
+            "γ" => Some(0.577_215_664_901_532_9),
             "c_m/s" => Some(299792458.0),
+            "∞" => Some(f64::INFINITY),
 
             "↉" => Some(0.0 / 3.0),
 
@@ -131,10 +256,17 @@ where
     Self: Sized,
 {
     fn execute(f: &str, args: &[Self]) -> Result<Self, &'static str>;
+
+    /// Whether [`ExecuteFunction::execute`] supports `f` with `arity` arguments. Partial
+    /// evaluation keeps calls of the functions that are not supported.
+    fn knows(f: &str, arity: usize) -> bool {
+        let _ = (f, arity);
+        true
+    }
 }
 
 macro_rules! map_fn {
-    ($type: ty: $($n: pat $(= $ac: tt => $map: tt ($($th: tt $th2: tt),*))? $(=> $f: expr)?),* $(,)?) => {
+    ($type: ty, $path: ident: $($n: pat $(= $ac: tt => $map: tt ($($th: tt $th2: tt),*))? $(=> $f: expr)?),* $(,)?) => {
         impl ExecuteFunction for $type {
             fn execute(f: &str, args: &[Self]) -> Result<Self, &'static str> {
                 fn check(v: $type) -> Result<$type, &'static str> {
@@ -143,12 +275,25 @@ macro_rules! map_fn {
 
                 match (f, args.len()) {
                     $(
-                        $(($n, $ac) => check(Self::$map($(emit!(args, $th $th2)),*).into()),)?
+                        $(($n, $ac) => check($path!(Self, $map)($(emit!(args, $th $th2)),*).into()),)?
                         $(($n, _) => $f(args),)?
                     )*
                     _ => Err("function not supported"),
                 }
             }
+
+            fn knows(f: &str, arity: usize) -> bool {
+                match (f, arity) {
+                    $(
+                        $(($n, $ac) => true,)?
+                        $(($n, _) => {
+                            let _: fn(&[Self]) -> Result<Self, &'static str> = $f;
+                            true
+                        },)?
+                    )*
+                    _ => false,
+                }
+            }
         }
     };
 }
@@ -160,15 +305,128 @@ macro_rules! emit {
 
 macro_rules! map_fns {
     (f $($t: tt)*) => {
-        map_fn!(f32: $($t)*);
-        map_fn!(f64: $($t)*);
+        map_fn!(f32, float: $($t)*);
+        map_fn!(f64, float: $($t)*);
     };
     (c $($t: tt)*) => {
-        map_fn!(num_complex::Complex<f32>: $($t)*);
-        map_fn!(num_complex::Complex<f64>: $($t)*);
+        map_fn!(num_complex::Complex<f32>, inherent: $($t)*);
+        map_fn!(num_complex::Complex<f64>, inherent: $($t)*);
     };
 }
 
+#[cfg(feature = "num_complex")]
+macro_rules! inherent {
+    ($type: ty, $f: ident) => { <$type>::$f };
+}
+
+/// A floating point function, from `std` or from [`LibmFloat`] on `no_std` targets. The trait is
+/// named, because the inherent methods would take precedence if any crate links `std`.
+#[cfg(feature = "std")]
+macro_rules! float {
+    ($type: ty, $f: ident) => { <$type>::$f };
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! float {
+    ($type: ty, $f: ident) => { <$type as LibmFloat>::$f };
+}
+
+pub(crate) use float;
+
+/// Floating point functions that `core` does not provide, backed by `libm` on `no_std` targets
+#[cfg(not(feature = "std"))]
+pub(crate) trait LibmFloat: Sized {
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn trunc(self) -> Self;
+    fn fract(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn cbrt(self) -> Self;
+    fn ln(self) -> Self;
+    fn log10(self) -> Self;
+    fn log(self, base: Self) -> Self;
+    fn powf(self, exp: Self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+    fn tanh(self) -> Self;
+    fn asinh(self) -> Self;
+    fn acosh(self) -> Self;
+    fn atanh(self) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! libm_float {
+    ($type: ty: $($f: ident ($($a: ident),*) => $libm: expr),* $(,)?) => {
+        impl LibmFloat for $type {
+            $(fn $f(self $(, $a: Self)*) -> Self { $libm(self $(, $a)*) })*
+        }
+    };
+}
+
+#[cfg(not(feature = "std"))]
+libm_float!(f32:
+    abs() => libm::fabsf,
+    floor() => libm::floorf,
+    ceil() => libm::ceilf,
+    round() => libm::roundf,
+    trunc() => libm::truncf,
+    fract() => |v| v - libm::truncf(v),
+    sqrt() => libm::sqrtf,
+    cbrt() => libm::cbrtf,
+    ln() => libm::logf,
+    log10() => libm::log10f,
+    log(base) => |v, base| libm::logf(v) / libm::logf(base),
+    powf(exp) => libm::powf,
+    sin() => libm::sinf,
+    cos() => libm::cosf,
+    tan() => libm::tanf,
+    asin() => libm::asinf,
+    acos() => libm::acosf,
+    atan() => libm::atanf,
+    sinh() => libm::sinhf,
+    cosh() => libm::coshf,
+    tanh() => libm::tanhf,
+    asinh() => libm::asinhf,
+    acosh() => libm::acoshf,
+    atanh() => libm::atanhf,
+);
+
+#[cfg(not(feature = "std"))]
+libm_float!(f64:
+    abs() => libm::fabs,
+    floor() => libm::floor,
+    ceil() => libm::ceil,
+    round() => libm::round,
+    trunc() => libm::trunc,
+    fract() => |v| v - libm::trunc(v),
+    sqrt() => libm::sqrt,
+    cbrt() => libm::cbrt,
+    ln() => libm::log,
+    log10() => libm::log10,
+    log(base) => |v, base| libm::log(v) / libm::log(base),
+    powf(exp) => libm::pow,
+    sin() => libm::sin,
+    cos() => libm::cos,
+    tan() => libm::tan,
+    asin() => libm::asin,
+    acos() => libm::acos,
+    atan() => libm::atan,
+    sinh() => libm::sinh,
+    cosh() => libm::cosh,
+    tanh() => libm::tanh,
+    asinh() => libm::asinh,
+    acosh() => libm::acosh,
+    atanh() => libm::atanh,
+);
+
 map_fns!(f
     "floor" = 1 => floor(0 .),
     "ceil" = 1 => ceil(0 .),
@@ -180,12 +438,12 @@ map_fns!(f
     "ln" = 1 => ln(0 .),
     "log" = 1 => log10(0 .),
     "log" = 2 => log(0 ., 1 .),
-    "min" => |args: &[Self]| if args.len() != 0 {
+    "min" => |args: &[Self]| if !args.is_empty() {
         Ok(args.iter().fold(Self::INFINITY, |a, &b| a.min(b)))
     } else {
         Err("expect ≥1 arguments")
     },
-    "max" => |args: &[Self]| if args.len() != 0 {
+    "max" => |args: &[Self]| if !args.is_empty() {
         Ok(args.iter().fold(Self::NEG_INFINITY, |a, &b| a.max(b)))
     } else {
         Err("expect ≥1 arguments")
@@ -0,0 +1,256 @@
+//! Walking and rewriting [`Node`] trees.
+//!
+//! Every method of a [`Visitor`] or [`MutVisitor`] walks into the children of its node by default,
+//! so an implementation only has to override the kinds of nodes it is interested in. An overriding
+//! method can call the matching `walk_*` function to keep walking, e.g. [`walk_bi_op`] for
+//! `visit_bi_op` or [`walk_call_mut`] for `visit_call_mut`.
+//!
+//! A [`Fold`] has walked into the children before its method for a node is called, so an
+//! overriding method gets the folded children and only has to build the node.
+
+use alloc::{boxed::Box, string::String};
+use crate::*;
+
+/// Looks at every node of a tree
+pub trait Visitor<'a, N> {
+    fn visit_node(&mut self, node: &'a Node<N>) { walk_node(self, node) }
+
+    fn visit_number(&mut self, _n: &'a N, _span: &'a Span) {}
+
+    fn visit_constant(&mut self, _c: &'a str, _span: &'a Span) {}
+
+    fn visit_bi_op(&mut self, l: &'a Node<N>, _op: BiOpr, r: &'a Node<N>, _span: &'a Span) { walk_bi_op(self, l, r) }
+
+    fn visit_un_op(&mut self, _op: UnOpr, v: &'a Node<N>, _span: &'a Span) { walk_un_op(self, v) }
+
+    fn visit_call(&mut self, c: &'a Call<N>, _span: &'a Span) { walk_call(self, c) }
+
+    fn visit_group(&mut self, _k: BKind, v: &'a Node<N>, _span: &'a Span) { walk_group(self, v) }
+}
+
+/// Calls the [`Visitor`] method for the kind of `node`
+pub fn walk_node<'a, N, V: Visitor<'a, N> + ?Sized>(v: &mut V, node: &'a Node<N>) {
+    let span = &node.span;
+
+    match &node.kind {
+        NodeKind::Number(n) => v.visit_number(n, span),
+        NodeKind::Constant(c) => v.visit_constant(c, span),
+        NodeKind::BiOp(l, op, r) => v.visit_bi_op(l, *op, r, span),
+        NodeKind::UnOp(op, a) => v.visit_un_op(*op, a, span),
+        NodeKind::Function(c) => v.visit_call(c, span),
+        NodeKind::Group(k, a) => v.visit_group(*k, a, span),
+    }
+}
+
+/// Visits both operands of a binary operation
+pub fn walk_bi_op<'a, N, V: Visitor<'a, N> + ?Sized>(v: &mut V, l: &'a Node<N>, r: &'a Node<N>) {
+    v.visit_node(l);
+    v.visit_node(r);
+}
+
+/// Visits the operand of a unary operation
+pub fn walk_un_op<'a, N, V: Visitor<'a, N> + ?Sized>(v: &mut V, a: &'a Node<N>) { v.visit_node(a) }
+
+/// Visits every argument of `c`
+pub fn walk_call<'a, N, V: Visitor<'a, N> + ?Sized>(v: &mut V, c: &'a Call<N>) {
+    for a in &c.args {
+        v.visit_node(a);
+    }
+}
+
+/// Visits the expression in brackets
+pub fn walk_group<'a, N, V: Visitor<'a, N> + ?Sized>(v: &mut V, a: &'a Node<N>) { v.visit_node(a) }
+
+/// Changes nodes of a tree in place
+pub trait MutVisitor<N> {
+    fn visit_node_mut(&mut self, node: &mut Node<N>) { walk_node_mut(self, node) }
+
+    fn visit_number_mut(&mut self, _n: &mut N, _span: &mut Span) {}
+
+    fn visit_constant_mut(&mut self, _c: &mut String, _span: &mut Span) {}
+
+    fn visit_bi_op_mut(&mut self, l: &mut Node<N>, _op: &mut BiOpr, r: &mut Node<N>, _span: &mut Span) {
+        walk_bi_op_mut(self, l, r)
+    }
+
+    fn visit_un_op_mut(&mut self, _op: &mut UnOpr, v: &mut Node<N>, _span: &mut Span) { walk_un_op_mut(self, v) }
+
+    fn visit_call_mut(&mut self, c: &mut Call<N>, _span: &mut Span) { walk_call_mut(self, c) }
+
+    fn visit_group_mut(&mut self, _k: &mut BKind, v: &mut Node<N>, _span: &mut Span) { walk_group_mut(self, v) }
+}
+
+/// Calls the [`MutVisitor`] method for the kind of `node`
+pub fn walk_node_mut<N, V: MutVisitor<N> + ?Sized>(v: &mut V, node: &mut Node<N>) {
+    let span = &mut node.span;
+
+    match &mut node.kind {
+        NodeKind::Number(n) => v.visit_number_mut(n, span),
+        NodeKind::Constant(c) => v.visit_constant_mut(c, span),
+        NodeKind::BiOp(l, op, r) => v.visit_bi_op_mut(l, op, r, span),
+        NodeKind::UnOp(op, a) => v.visit_un_op_mut(op, a, span),
+        NodeKind::Function(c) => v.visit_call_mut(c, span),
+        NodeKind::Group(k, a) => v.visit_group_mut(k, a, span),
+    }
+}
+
+/// Visits both operands of a binary operation
+pub fn walk_bi_op_mut<N, V: MutVisitor<N> + ?Sized>(v: &mut V, l: &mut Node<N>, r: &mut Node<N>) {
+    v.visit_node_mut(l);
+    v.visit_node_mut(r);
+}
+
+/// Visits the operand of a unary operation
+pub fn walk_un_op_mut<N, V: MutVisitor<N> + ?Sized>(v: &mut V, a: &mut Node<N>) { v.visit_node_mut(a) }
+
+/// Visits every argument of `c`
+pub fn walk_call_mut<N, V: MutVisitor<N> + ?Sized>(v: &mut V, c: &mut Call<N>) {
+    for a in &mut c.args {
+        v.visit_node_mut(a);
+    }
+}
+
+/// Visits the expression in brackets
+pub fn walk_group_mut<N, V: MutVisitor<N> + ?Sized>(v: &mut V, a: &mut Node<N>) { v.visit_node_mut(a) }
+
+/// Rebuilds a tree, the children of a node are folded before the node itself
+pub trait Fold<N> {
+    fn fold_node(&mut self, node: Node<N>) -> Node<N> { walk_fold(self, node) }
+
+    fn fold_number(&mut self, n: N, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::Number(n),
+            span,
+        }
+    }
+
+    fn fold_constant(&mut self, c: String, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::Constant(c),
+            span,
+        }
+    }
+
+    fn fold_bi_op(&mut self, l: Node<N>, op: BiOpr, r: Node<N>, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::BiOp(Box::new(l), op, Box::new(r)),
+            span,
+        }
+    }
+
+    fn fold_un_op(&mut self, op: UnOpr, v: Node<N>, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::UnOp(op, Box::new(v)),
+            span,
+        }
+    }
+
+    fn fold_call(&mut self, c: Call<N>, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::Function(c),
+            span,
+        }
+    }
+
+    fn fold_group(&mut self, k: BKind, v: Node<N>, span: Span) -> Node<N> {
+        Node {
+            kind: NodeKind::Group(k, Box::new(v)),
+            span,
+        }
+    }
+}
+
+/// Folds the children of `node`, then calls the [`Fold`] method for its kind
+pub fn walk_fold<N, F: Fold<N> + ?Sized>(f: &mut F, node: Node<N>) -> Node<N> {
+    let span = node.span;
+
+    match node.kind {
+        NodeKind::Number(n) => f.fold_number(n, span),
+        NodeKind::Constant(c) => f.fold_constant(c, span),
+        NodeKind::BiOp(l, op, r) => {
+            let l = f.fold_node(*l);
+            let r = f.fold_node(*r);
+            f.fold_bi_op(l, op, r, span)
+        },
+        NodeKind::UnOp(op, v) => {
+            let v = f.fold_node(*v);
+            f.fold_un_op(op, v, span)
+        },
+        NodeKind::Function(mut c) => {
+            c.args = c.args.into_iter().map(|a| f.fold_node(a)).collect();
+            f.fold_call(c, span)
+        },
+        NodeKind::Group(k, v) => {
+            let v = f.fold_node(*v);
+            f.fold_group(k, v, span)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn visitor() {
+        struct Constants<'a>(Vec<&'a str>);
+
+        impl<'a> Visitor<'a, f64> for Constants<'a> {
+            fn visit_constant(&mut self, c: &'a str, _span: &'a Span) { self.0.push(c); }
+        }
+
+        let n = to_nodes::<f64>("π + max(e, 2τ) / (1 - φ)").unwrap();
+        let mut v = Constants(Vec::new());
+        v.visit_node(&n);
+        assert_eq!(v.0, ["π", "e", "τ", "φ"]);
+    }
+
+    #[test]
+    fn mut_visitor() {
+        struct Double;
+
+        impl MutVisitor<f64> for Double {
+            fn visit_number_mut(&mut self, n: &mut f64, _span: &mut Span) { *n *= 2.0; }
+
+            fn visit_bi_op_mut(&mut self, l: &mut Node<f64>, op: &mut BiOpr, r: &mut Node<f64>, _span: &mut Span) {
+                if *op == BiOpr::Power {
+                    // leave exponents alone
+                    return self.visit_node_mut(l);
+                }
+
+                walk_bi_op_mut(self, l, r);
+            }
+        }
+
+        let mut n = to_nodes::<f64>("1 + sqrt(2)^2").unwrap();
+        Double.visit_node_mut(&mut n);
+        assert_eq!(n.to_string(), "2 + sqrt(4)^2");
+    }
+
+    #[test]
+    fn fold() {
+        /// Removes brackets and multiplications by one
+        struct Tidy;
+
+        impl Fold<f64> for Tidy {
+            fn fold_group(&mut self, _k: BKind, v: Node<f64>, _span: Span) -> Node<f64> { v }
+
+            fn fold_bi_op(&mut self, l: Node<f64>, op: BiOpr, r: Node<f64>, span: Span) -> Node<f64> {
+                match (&l.kind, op, &r.kind) {
+                    (_, BiOpr::Multiply | BiOpr::ImplicitMultiply, NodeKind::Number(n)) if *n == 1.0 => l,
+                    (NodeKind::Number(n), BiOpr::Multiply | BiOpr::ImplicitMultiply, _) if *n == 1.0 => r,
+                    _ => Node {
+                        kind: NodeKind::BiOp(Box::new(l), op, Box::new(r)),
+                        span,
+                    },
+                }
+            }
+        }
+
+        let n = to_nodes::<f64>("((2 * 1) + 1(3))*(1)").unwrap();
+        let n = Tidy.fold_node(n);
+        assert_eq!(n.to_string(), "2 + 3");
+        assert!(matches!(n.kind, NodeKind::BiOp(..)));
+    }
+}